@@ -12,6 +12,8 @@ pub enum Slot {
     Global,
     Table,
     Elem,
+    Memory,
+    Data,
 }
 
 pub trait Slottable {
@@ -39,6 +41,10 @@ impl Addr {
     pub fn element_index(&self) -> ElemIndex {
         self.1
     }
+
+    pub fn slot(&self) -> Slot {
+        self.2
+    }
 }
 
 pub struct Addressable<T: Slottable> {
@@ -63,11 +69,11 @@ impl<T: Slottable> Addressable<T> {
     }
 
     pub fn get(&self, addr: &Addr) -> Option<&T> {
-        self.addresses.get(&addr)
+        self.addresses.get(addr)
     }
 
     pub fn get_mut(&mut self, addr: &Addr) -> Option<&mut T> {
-        self.addresses.get_mut(&addr)
+        self.addresses.get_mut(addr)
     }
 }
 
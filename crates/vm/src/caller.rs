@@ -0,0 +1,43 @@
+use crate::{
+    instance::{Index as InstanceIndex, Instance, Memory},
+    store::Store,
+};
+use anyhow::Result;
+
+/// The context in which a host function is invoked, giving access
+/// to the store and to the instance that called the function
+pub struct Caller<'a> {
+    store: &'a mut Store,
+    instance: InstanceIndex,
+}
+
+impl<'a> Caller<'a> {
+    pub(crate) fn new(store: &'a mut Store, instance: InstanceIndex) -> Self {
+        Self { store, instance }
+    }
+
+    pub fn store(&self) -> &Store {
+        self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut Store {
+        self.store
+    }
+
+    /// The instance from which the host function was called
+    pub fn instance(&self) -> Result<&Instance> {
+        self.store.instance(self.instance)
+    }
+
+    /// The memory at the given index of the calling instance
+    pub fn memory(&self, index: u32) -> Result<&Memory> {
+        let addr = self.instance()?.memory_addr(index)?;
+        self.store.memory(&addr)
+    }
+
+    /// The memory at the given index of the calling instance
+    pub fn memory_mut(&mut self, index: u32) -> Result<&mut Memory> {
+        let addr = self.instance()?.memory_addr(index)?;
+        self.store.memory_mut(&addr)
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{bail, Result};
+use wasmparser::WasmFeatures;
+
+/// The set of WebAssembly proposals accepted when parsing and
+/// validating modules, and when instantiating them in a store,
+/// along with the execution options of stores.
+///
+/// Multi-value, bulk memory and reference types are enabled by default,
+/// and multi-memory and extended constant expressions can be enabled.
+/// The other proposals are not supported by the interpreter; modules
+/// can't be parsed with any of them enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    multi_value: bool,
+    bulk_memory: bool,
+    reference_types: bool,
+    simd: bool,
+    relaxed_simd: bool,
+    tail_call: bool,
+    exceptions: bool,
+    threads: bool,
+    memory64: bool,
+    multi_memory: bool,
+    extended_const: bool,
+    consume_fuel: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            multi_value: true,
+            bulk_memory: true,
+            reference_types: true,
+            simd: false,
+            relaxed_simd: false,
+            tail_call: false,
+            exceptions: false,
+            threads: false,
+            memory64: false,
+            multi_memory: false,
+            extended_const: false,
+            consume_fuel: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn wasm_multi_value(&mut self, enable: bool) -> &mut Self {
+        self.multi_value = enable;
+        self
+    }
+
+    pub fn wasm_bulk_memory(&mut self, enable: bool) -> &mut Self {
+        self.bulk_memory = enable;
+        self
+    }
+
+    /// The reference types proposal builds on bulk memory; modules
+    /// are rejected if it's enabled without bulk memory
+    pub fn wasm_reference_types(&mut self, enable: bool) -> &mut Self {
+        self.reference_types = enable;
+        self
+    }
+
+    pub fn wasm_simd(&mut self, enable: bool) -> &mut Self {
+        self.simd = enable;
+        self
+    }
+
+    pub fn wasm_relaxed_simd(&mut self, enable: bool) -> &mut Self {
+        self.relaxed_simd = enable;
+        self
+    }
+
+    pub fn wasm_tail_call(&mut self, enable: bool) -> &mut Self {
+        self.tail_call = enable;
+        self
+    }
+
+    pub fn wasm_exceptions(&mut self, enable: bool) -> &mut Self {
+        self.exceptions = enable;
+        self
+    }
+
+    pub fn wasm_threads(&mut self, enable: bool) -> &mut Self {
+        self.threads = enable;
+        self
+    }
+
+    pub fn wasm_memory64(&mut self, enable: bool) -> &mut Self {
+        self.memory64 = enable;
+        self
+    }
+
+    pub fn wasm_multi_memory(&mut self, enable: bool) -> &mut Self {
+        self.multi_memory = enable;
+        self
+    }
+
+    pub fn wasm_extended_const(&mut self, enable: bool) -> &mut Self {
+        self.extended_const = enable;
+        self
+    }
+
+    /// Whether stores meter the execution of instructions with fuel;
+    /// stores start without any fuel
+    pub fn consume_fuel(&mut self, enable: bool) -> &mut Self {
+        self.consume_fuel = enable;
+        self
+    }
+
+    pub fn fuel_enabled(&self) -> bool {
+        self.consume_fuel
+    }
+
+    /// The features handed to the validator
+    pub fn features(&self) -> WasmFeatures {
+        WasmFeatures {
+            multi_value: self.multi_value,
+            bulk_memory: self.bulk_memory,
+            reference_types: self.reference_types,
+            simd: self.simd,
+            relaxed_simd: self.relaxed_simd,
+            tail_call: self.tail_call,
+            exceptions: self.exceptions,
+            threads: self.threads,
+            memory64: self.memory64,
+            multi_memory: self.multi_memory,
+            extended_const: self.extended_const,
+            ..WasmFeatures::default()
+        }
+    }
+
+    /// Checks that the enabled proposals are supported by the
+    /// interpreter and consistent with each other
+    pub(crate) fn check(&self) -> Result<()> {
+        let unsupported = [
+            ("SIMD", self.simd),
+            ("relaxed SIMD", self.relaxed_simd),
+            ("tail call", self.tail_call),
+            ("exception handling", self.exceptions),
+            ("threads", self.threads),
+            ("memory64", self.memory64),
+        ];
+        if let Some((proposal, _)) = unsupported.iter().find(|(_, enabled)| *enabled) {
+            bail!(
+                "The {} proposal is not supported by this interpreter",
+                proposal
+            );
+        }
+        if self.reference_types && !self.bulk_memory {
+            bail!("The reference types proposal requires the bulk memory proposal");
+        }
+
+        Ok(())
+    }
+
+    /// Whether every proposal enabled in `other` is also enabled here;
+    /// execution options are not compared
+    pub fn includes(&self, other: &Config) -> bool {
+        let enabled = |this: bool, other: bool| this || !other;
+
+        enabled(self.multi_value, other.multi_value)
+            && enabled(self.bulk_memory, other.bulk_memory)
+            && enabled(self.reference_types, other.reference_types)
+            && enabled(self.simd, other.simd)
+            && enabled(self.relaxed_simd, other.relaxed_simd)
+            && enabled(self.tail_call, other.tail_call)
+            && enabled(self.exceptions, other.exceptions)
+            && enabled(self.threads, other.threads)
+            && enabled(self.memory64, other.memory64)
+            && enabled(self.multi_memory, other.multi_memory)
+            && enabled(self.extended_const, other.extended_const)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::{module::Module, store::Store};
+
+    #[test]
+    fn includes() {
+        let default = Config::new();
+        let mut simd = Config::new();
+        simd.wasm_simd(true);
+        let mut mvp = Config::new();
+        mvp.wasm_multi_value(false)
+            .wasm_bulk_memory(false)
+            .wasm_reference_types(false);
+
+        assert!(default.includes(&default));
+        assert!(simd.includes(&default));
+        assert!(!default.includes(&simd));
+        assert!(default.includes(&mvp));
+        assert!(!mvp.includes(&default));
+    }
+
+    #[test]
+    fn check() {
+        let bytes = wat::parse_str(r#"(module)"#).unwrap();
+        let unsupported: [fn(&mut Config) -> &mut Config; 6] = [
+            |config| config.wasm_simd(true),
+            |config| config.wasm_relaxed_simd(true),
+            |config| config.wasm_tail_call(true),
+            |config| config.wasm_exceptions(true),
+            |config| config.wasm_threads(true),
+            |config| config.wasm_memory64(true),
+        ];
+        for enable in unsupported {
+            let mut config = Config::new();
+            enable(&mut config);
+            let err = Module::from_binary_with_config(&bytes, &config).unwrap_err();
+            assert!(err
+                .to_string()
+                .ends_with("proposal is not supported by this interpreter"));
+        }
+
+        let mut config = Config::new();
+        config.wasm_bulk_memory(false);
+        assert!(Module::from_binary_with_config(&bytes, &config).is_err());
+        config.wasm_reference_types(false);
+        assert!(Module::from_binary_with_config(&bytes, &config).is_ok());
+    }
+
+    #[test]
+    fn store_config() {
+        let bytes = wat::parse_str(r#"(module (func (export "f")))"#).unwrap();
+        let mut config = Config::new();
+        config.wasm_extended_const(true);
+        let module = Module::from_binary_with_config(&bytes, &config).unwrap();
+
+        assert!(Store::new().instantiate(&module, None).is_err());
+        assert!(Store::with_config(config)
+            .instantiate(&module, None)
+            .is_ok());
+    }
+}
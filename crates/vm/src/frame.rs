@@ -1,8 +1,81 @@
-use crate::val::Value;
+use crate::{
+    addressable::Addr,
+    instance::{Body, WasmFunc},
+    val::{Value, ValueType},
+};
+use anyhow::{Context, Result};
+use std::rc::Rc;
 
+/// The activation of a function
 pub struct Frame {
     locals: Vec<Value>,
     // TODO: Replace with a definition
     // of Instruction Pointer if there's one
+    //
+    // The instruction pointer of the frame, saved when
+    // the frame is pushed to the stack on a call
     ip: usize,
+    func: Addr,
+    arity: usize,
+    body: Rc<Body>,
+}
+
+impl Frame {
+    /// Creates a new frame for the function at the given address;
+    /// the locals are made of the arguments followed by the default
+    /// values of the declared locals
+    pub fn new(addr: Addr, func: &WasmFunc, args: Vec<Value>) -> Result<Self> {
+        let mut locals = args;
+        locals.reserve_exact(func.locals().len());
+        for ty in func.locals() {
+            locals.push(Value::default_for(ValueType::try_from(*ty)?));
+        }
+
+        Ok(Self {
+            locals,
+            ip: 0,
+            func: addr,
+            arity: func.ty().returns.len(),
+            body: func.body(),
+        })
+    }
+
+    pub fn local(&self, index: u32) -> Result<Value> {
+        self.locals
+            .get(index as usize)
+            .copied()
+            .with_context(|| format!("Invalid local index {}", index))
+    }
+
+    pub fn set_local(&mut self, index: u32, val: Value) -> Result<()> {
+        let local = self
+            .locals
+            .get_mut(index as usize)
+            .with_context(|| format!("Invalid local index {}", index))?;
+        *local = val;
+
+        Ok(())
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn set_ip(&mut self, ip: usize) {
+        self.ip = ip;
+    }
+
+    /// The address of the function being executed
+    pub fn func(&self) -> Addr {
+        self.func
+    }
+
+    /// The number of values returned by the function
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn body(&self) -> Rc<Body> {
+        Rc::clone(&self.body)
+    }
 }
@@ -0,0 +1,192 @@
+use crate::instr::Kind;
+
+/// The amount of fuel charged for executing an instruction,
+/// consulted by the store when fuel consumption is enabled
+pub trait FuelCosts {
+    fn cost(&self, kind: &Kind) -> u64;
+}
+
+/// Charges one unit of fuel per instruction, except for the
+/// instructions which only delimit or discard and are free
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultFuelCosts;
+
+impl FuelCosts for DefaultFuelCosts {
+    fn cost(&self, kind: &Kind) -> u64 {
+        match kind {
+            Kind::Nop
+            | Kind::Drop
+            | Kind::Block { .. }
+            | Kind::Loop { .. }
+            | Kind::Unreachable
+            | Kind::Return
+            | Kind::Else
+            | Kind::End => 0,
+            _ => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FuelCosts;
+    use crate::{
+        addressable::Addr,
+        caller::Caller,
+        config::Config,
+        instr::Kind,
+        linker::Linker,
+        module::Module,
+        store::Store,
+        trap::{Trap, TrapCode},
+        val::Value,
+        vm::VM,
+    };
+
+    const WAT: &str = r#"(module
+                           (func (export "sum") (param $n i32) (result i32) (local $acc i32)
+                             (block $done
+                               (loop $next
+                                 local.get $n
+                                 i32.eqz
+                                 br_if $done
+                                 local.get $acc
+                                 local.get $n
+                                 i32.add
+                                 local.set $acc
+                                 local.get $n
+                                 i32.const 1
+                                 i32.sub
+                                 local.set $n
+                                 br $next))
+                             local.get $acc))"#;
+
+    fn fueled(bytes: &[u8], fuel: u64) -> (Store, Addr) {
+        let module = Module::from_binary(bytes).unwrap();
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let mut store = Store::with_config(config);
+        store.add_fuel(fuel).unwrap();
+        let instance = store.instantiate(&module, None).unwrap();
+        let addr = instance.get_func(&store, "sum").unwrap().addr();
+
+        (store, addr)
+    }
+
+    #[test]
+    fn fuel_disabled() {
+        let mut store = Store::new();
+        assert_eq!(store.fuel_remaining(), None);
+        assert!(store.add_fuel(1).is_err());
+        assert!(store.consume_fuel(0).is_err());
+    }
+
+    #[test]
+    fn consume_fuel() {
+        let bytes = wat::parse_str(WAT).unwrap();
+        let (mut store, _) = fueled(&bytes, 5);
+
+        assert_eq!(store.consume_fuel(3).unwrap(), 2);
+        assert!(store.consume_fuel(3).is_err());
+        assert_eq!(store.fuel_remaining(), Some(2));
+        store.add_fuel(u64::MAX).unwrap();
+        assert_eq!(store.fuel_remaining(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn out_of_fuel_and_resume() {
+        let bytes = wat::parse_str(WAT).unwrap();
+        let args = [Value::I32(10)];
+
+        let (mut store, addr) = fueled(&bytes, 10_000);
+        let results = VM::new().invoke(&mut store, addr, &args).unwrap();
+        assert_eq!(results, vec![Value::I32(55)]);
+        let consumed = 10_000 - store.fuel_remaining().unwrap();
+
+        let (mut store, addr) = fueled(&bytes, 10);
+        let mut vm = VM::new();
+        let err = vm.invoke(&mut store, addr, &args).unwrap_err();
+        let trap = err.downcast_ref::<Trap>().unwrap();
+        assert_eq!(trap.code(), TrapCode::OutOfFuel);
+        assert_eq!(trap.backtrace().len(), 1);
+        let left = store.fuel_remaining().unwrap();
+
+        // Resuming charges the remaining instructions exactly once
+        store.add_fuel(10_000).unwrap();
+        assert_eq!(vm.resume(&mut store).unwrap(), vec![Value::I32(55)]);
+        let resumed = 10 - left + 10_000 - store.fuel_remaining().unwrap();
+        assert_eq!(resumed, consumed);
+        assert!(vm.resume(&mut store).is_err());
+    }
+
+    #[test]
+    fn fuel_costs() {
+        struct AddsOnly;
+
+        impl FuelCosts for AddsOnly {
+            fn cost(&self, kind: &Kind) -> u64 {
+                match kind {
+                    Kind::I32Add => 100,
+                    _ => 0,
+                }
+            }
+        }
+
+        let bytes = wat::parse_str(WAT).unwrap();
+        let (mut store, addr) = fueled(&bytes, 150);
+        store.set_fuel_costs(AddsOnly);
+
+        let results = VM::new()
+            .invoke(&mut store, addr, &[Value::I32(1)])
+            .unwrap();
+        assert_eq!(results, vec![Value::I32(1)]);
+        assert_eq!(store.fuel_remaining(), Some(50));
+
+        let err = VM::new()
+            .invoke(&mut store, addr, &[Value::I32(2)])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::OutOfFuel)
+        );
+        assert_eq!(store.fuel_remaining(), Some(50));
+    }
+
+    #[test]
+    fn out_of_fuel_in_host_call() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (import "host" "call" (func $call))
+                 (func (export "outer") call $call)
+                 (func (export "inner") (loop br 0)))"#,
+        )
+        .unwrap();
+        let mut linker = Linker::new();
+        linker
+            .typed_func("host", "call", |mut caller: Caller<'_>, ()| {
+                let instance = caller.instance()?.clone();
+                let inner = instance.get_func(caller.store(), "inner")?;
+                inner.call(caller.store_mut(), &[])?;
+                Ok(())
+            })
+            .unwrap();
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let mut store = Store::with_config(config);
+        store.add_fuel(100).unwrap();
+        let instance = linker
+            .instantiate(&mut store, &Module::from_binary(&bytes).unwrap(), None)
+            .unwrap();
+        let outer = instance.get_func(&store, "outer").unwrap();
+
+        let mut vm = VM::new();
+        let err = vm.invoke(&mut store, outer.addr(), &[]).unwrap_err();
+        let trap = err.downcast_ref::<Trap>().unwrap();
+        assert_eq!(trap.code(), TrapCode::OutOfFuel);
+        assert_eq!(trap.backtrace().len(), 2);
+
+        // The nested invocation is gone along with the host function
+        store.add_fuel(100).unwrap();
+        assert!(vm.resume(&mut store).is_err());
+    }
+}
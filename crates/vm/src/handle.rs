@@ -0,0 +1,229 @@
+use crate::{
+    addressable::Addr,
+    instance::{Memory, Table},
+    store::Store,
+    val::{RefValue, Value, ValueType},
+    vm::VM,
+};
+use anyhow::{bail, Result};
+use wasmparser::{FuncType, GlobalType};
+
+/// A function in a store, usually obtained through an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuncHandle(Addr);
+
+impl FuncHandle {
+    pub(crate) fn new(addr: Addr) -> Self {
+        Self(addr)
+    }
+
+    pub fn addr(&self) -> Addr {
+        self.0
+    }
+
+    pub fn ty(&self, store: &Store) -> Result<FuncType> {
+        Ok(store.func(&self.0)?.ty().clone())
+    }
+
+    /// Invokes the function with the given arguments
+    pub fn call(&self, store: &mut Store, args: &[Value]) -> Result<Vec<Value>> {
+        VM::new().invoke(store, self.0, args)
+    }
+}
+
+/// A global in a store, usually obtained through an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobalHandle(Addr);
+
+impl GlobalHandle {
+    pub(crate) fn new(addr: Addr) -> Self {
+        Self(addr)
+    }
+
+    pub fn addr(&self) -> Addr {
+        self.0
+    }
+
+    pub fn ty(&self, store: &Store) -> Result<GlobalType> {
+        Ok(store.global(&self.0)?.ty())
+    }
+
+    pub fn get(&self, store: &Store) -> Result<Value> {
+        Ok(store.global(&self.0)?.value())
+    }
+
+    /// Sets the value of a mutable global
+    pub fn set(&self, store: &mut Store, val: Value) -> Result<()> {
+        let global = store.global_mut(&self.0)?;
+        if !global.ty().mutable {
+            bail!("Cannot set the value of an immutable global");
+        }
+        let expected = ValueType::try_from(global.ty().content_type)?;
+        if expected != val.ty() {
+            bail!("Type mismatch: expected {:?}, got {:?}", expected, val.ty());
+        }
+
+        global.set(val);
+
+        Ok(())
+    }
+}
+
+/// A table in a store, usually obtained through an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableHandle(Addr);
+
+impl TableHandle {
+    pub(crate) fn new(addr: Addr) -> Self {
+        Self(addr)
+    }
+
+    pub fn addr(&self) -> Addr {
+        self.0
+    }
+
+    pub fn table<'s>(&self, store: &'s Store) -> Result<&'s Table> {
+        store.table(&self.0)
+    }
+
+    /// Sets the element at the given index, which must be
+    /// of the element type of the table
+    pub fn set(&self, store: &mut Store, index: u32, val: RefValue) -> Result<()> {
+        let table = store.table_mut(&self.0)?;
+        check_elem_type(table, val)?;
+        table.set(index, val)?;
+
+        Ok(())
+    }
+
+    /// Grows the table, consulting the resource limiter of the
+    /// store; returns the previous size on success
+    pub fn grow(&self, store: &mut Store, delta: u32, init: RefValue) -> Result<Option<u32>> {
+        check_elem_type(store.table(&self.0)?, init)?;
+        store.grow_table(&self.0, delta, init)
+    }
+}
+
+/// A memory in a store, usually obtained through an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryHandle(Addr);
+
+impl MemoryHandle {
+    pub(crate) fn new(addr: Addr) -> Self {
+        Self(addr)
+    }
+
+    pub fn addr(&self) -> Addr {
+        self.0
+    }
+
+    pub fn memory<'s>(&self, store: &'s Store) -> Result<&'s Memory> {
+        store.memory(&self.0)
+    }
+
+    /// Writes the bytes to the memory at the given address
+    pub fn write(&self, store: &mut Store, addr: u64, bytes: &[u8]) -> Result<()> {
+        store.memory_mut(&self.0)?.write(addr, bytes)?;
+
+        Ok(())
+    }
+
+    /// Grows the memory by the given number of pages, consulting the
+    /// resource limiter of the store; returns the previous size on success
+    pub fn grow(&self, store: &mut Store, delta: u64) -> Result<Option<u64>> {
+        store.grow_memory(&self.0, delta)
+    }
+}
+
+fn check_elem_type(table: &Table, val: RefValue) -> Result<()> {
+    if table.ty() != val.ty() {
+        bail!(
+            "Type mismatch: expected {:?}, got {:?}",
+            table.ty(),
+            val.ty()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        module::Module,
+        store::Store,
+        val::{RefType, RefValue, Value},
+    };
+
+    const WAT: &str = r#"(module
+                           (global $g (export "counter") (mut i32) (i32.const 1))
+                           (global (export "const") i64 (i64.const 2))
+                           (table (export "table") 1 funcref)
+                           (memory (export "memory") 1)
+                           (data (i32.const 0) "\2a")
+                           (func (export "bump") (result i32)
+                             global.get $g
+                             i32.const 1
+                             i32.add
+                             global.set $g
+                             global.get $g))"#;
+
+    #[test]
+    fn exports() {
+        let bytes = wat::parse_str(WAT).unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let mut store = Store::new();
+        let instance = store.instantiate(&module, None).unwrap();
+
+        let bump = instance.get_func(&store, "bump").unwrap();
+        assert_eq!(bump.ty(&store).unwrap().params.len(), 0);
+        assert_eq!(bump.call(&mut store, &[]).unwrap(), vec![Value::I32(2)]);
+
+        let counter = instance.get_global(&store, "counter").unwrap();
+        assert_eq!(counter.get(&store).unwrap(), Value::I32(2));
+        counter.set(&mut store, Value::I32(41)).unwrap();
+        assert!(counter.set(&mut store, Value::I64(0)).is_err());
+        assert_eq!(bump.call(&mut store, &[]).unwrap(), vec![Value::I32(42)]);
+
+        let constant = instance.get_global(&store, "const").unwrap();
+        assert_eq!(constant.get(&store).unwrap(), Value::I64(2));
+        assert!(constant.set(&mut store, Value::I64(3)).is_err());
+
+        let table = instance.get_table(&store, "table").unwrap();
+        let null = RefValue::Null(RefType::FuncRef);
+        assert_eq!(table.grow(&mut store, 2, null).unwrap(), Some(1));
+        assert_eq!(table.table(&store).unwrap().size(), 3);
+        assert!(table.grow(&mut store, 1, RefValue::ExternRef).is_err());
+        let bump_ref = RefValue::FuncRef(bump.addr());
+        table.set(&mut store, 2, bump_ref).unwrap();
+        assert_eq!(table.table(&store).unwrap().get(2), Some(bump_ref));
+        assert!(table.set(&mut store, 0, RefValue::ExternRef).is_err());
+        assert!(table.set(&mut store, 3, null).is_err());
+
+        let memory = instance.get_memory(&store, "memory").unwrap();
+        assert_eq!(memory.memory(&store).unwrap().read::<1>(0), Ok([42]));
+        memory.write(&mut store, 0, &[7]).unwrap();
+        assert_eq!(memory.memory(&store).unwrap().read::<1>(0), Ok([7]));
+        assert!(memory.write(&mut store, 65535, &[0, 0]).is_err());
+        assert_eq!(memory.grow(&mut store, 1).unwrap(), Some(1));
+        assert_eq!(memory.memory(&store).unwrap().size(), 2);
+    }
+
+    #[test]
+    fn missing_exports() {
+        let bytes = wat::parse_str(WAT).unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let mut store = Store::new();
+        let instance = store.instantiate(&module, None).unwrap();
+
+        let err = instance.get_func(&store, "missing").unwrap_err();
+        assert_eq!(err.to_string(), "Unknown export missing");
+        let err = instance.get_func(&store, "memory").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Export memory is a Memory, expected a Func"
+        );
+        // Handles are bound to the store owning the instance
+        assert!(instance.get_memory(&Store::new(), "memory").is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::Result;
+
+use crate::{
+    addressable::{Slot, Slottable},
+    instance::Instance,
+    val::Value,
+    vm,
+};
+
+use wasmparser::DataKind;
+
+#[derive(Debug)]
+pub enum DataSegmentKind {
+    Passive,
+    Active { index: u32, offset: Value },
+}
+
+#[derive(Debug)]
+pub struct Data {
+    bytes: Vec<u8>,
+    kind: DataSegmentKind,
+}
+
+impl Data {
+    pub fn new(
+        bytes: Vec<u8>,
+        kind: &DataKind,
+        globals: &[Value],
+        instance: &Instance,
+    ) -> Result<Self> {
+        let kind = match kind {
+            DataKind::Passive => DataSegmentKind::Passive,
+            DataKind::Active {
+                memory_index: idx,
+                init_expr: operator,
+            } => {
+                let val = vm::resolve_constant_expr(operator, globals, instance)?;
+                DataSegmentKind::Active {
+                    index: *idx,
+                    offset: val,
+                }
+            }
+        };
+
+        Ok(Self { bytes, kind })
+    }
+
+    /// Is this data segment active?
+    pub fn is_active(&self) -> bool {
+        matches!(self.kind, DataSegmentKind::Active { .. })
+    }
+
+    /// Retrieve the metadata associated with an active data segment
+    pub fn metadata(&self) -> Option<(u32, &Value)> {
+        match &self.kind {
+            DataSegmentKind::Active { index, offset } => Some((*index, offset)),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Drops the contents of the segment, which
+    /// behaves as an empty segment afterwards
+    pub fn clear(&mut self) {
+        self.bytes = Vec::new();
+    }
+}
+
+impl Slottable for Data {
+    fn slot() -> Slot {
+        Slot::Data
+    }
+}
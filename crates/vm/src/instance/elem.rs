@@ -2,6 +2,7 @@ use anyhow::Result;
 
 use crate::{
     addressable::{Slot, Slottable},
+    instance::Instance,
     val::{RefType, RefValue, Value},
     vm,
 };
@@ -18,12 +19,18 @@ pub enum ElemKind {
 #[derive(Debug)]
 pub struct Elem {
     ty: RefType,
-    pub data: Vec<RefValue>,
+    data: Vec<RefValue>,
     kind: ElemKind,
 }
 
 impl Elem {
-    pub fn new(ty: RefType, data: Vec<RefValue>, kind: &ElementKind) -> Result<Self> {
+    pub fn new(
+        ty: RefType,
+        data: Vec<RefValue>,
+        kind: &ElementKind,
+        globals: &[Value],
+        instance: &Instance,
+    ) -> Result<Self> {
         let kind = match kind {
             ElementKind::Passive => ElemKind::Passive,
             ElementKind::Declared => ElemKind::Declared,
@@ -31,7 +38,7 @@ impl Elem {
                 table_index: idx,
                 init_expr: operator,
             } => {
-                let val = vm::resolve_constant_expr(&operator)?;
+                let val = vm::resolve_constant_expr(operator, globals, instance)?;
                 ElemKind::Active {
                     index: *idx,
                     offset: val,
@@ -44,10 +51,26 @@ impl Elem {
 
     /// Is this element segment active?
     pub fn is_active(&self) -> bool {
-        match self.kind {
-            ElemKind::Active { .. } => true,
-            _ => false,
-        }
+        matches!(self.kind, ElemKind::Active { .. })
+    }
+
+    /// Is this element segment passive?
+    pub fn is_passive(&self) -> bool {
+        matches!(self.kind, ElemKind::Passive)
+    }
+
+    pub fn ty(&self) -> RefType {
+        self.ty
+    }
+
+    pub fn data(&self) -> &[RefValue] {
+        &self.data
+    }
+
+    /// Drops the contents of the segment, which
+    /// behaves as an empty segment afterwards
+    pub fn clear(&mut self) {
+        self.data = Vec::new();
     }
 
     /// Retrieve the metada associated with an active data segment
@@ -1,17 +1,42 @@
 use crate::{
     addressable::{Slot, Slottable},
-    instr::Instr,
+    caller::Caller,
+    instr::{Instr, Kind},
+    val::{Value, ValueType},
 };
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use std::rc::Rc;
 use wasmparser::{FuncType, LocalsReader, OperatorsReader, Type};
 
-pub struct Func {
+/// A function instance, either defined by a module
+/// or provided by the host
+pub enum Func {
+    Wasm(WasmFunc),
+    Host(HostFunc),
+}
+
+impl Func {
+    pub fn ty(&self) -> &FuncType {
+        match self {
+            Func::Wasm(func) => func.ty(),
+            Func::Host(func) => func.ty(),
+        }
+    }
+}
+
+impl Slottable for Func {
+    fn slot() -> Slot {
+        Slot::Func
+    }
+}
+
+pub struct WasmFunc {
     ty: FuncType,
     locals: Vec<Type>,
-    body: Vec<Instr>,
+    body: Rc<Body>,
 }
 
-impl<'a> Func {
+impl<'a> WasmFunc {
     pub fn new(ty: FuncType, locals: LocalsReader, body: OperatorsReader<'a>) -> Result<Self> {
         Ok(Self {
             ty,
@@ -26,18 +51,232 @@ impl<'a> Func {
                     Ok(acc)
                 })?,
 
-            body: body
-                .into_iter()
-                .try_fold(Vec::new(), |mut acc, op| -> Result<_> {
-                    acc.push(Instr::try_from(op?)?);
+            body: Rc::new(Body::new(body.into_iter_with_offsets().try_fold(
+                Vec::new(),
+                |mut acc, op| -> Result<_> {
+                    let (op, offset) = op?;
+                    acc.push((Instr::try_from(op)?, offset));
                     Ok(acc)
-                })?,
+                },
+            )?)?),
         })
     }
+
+    pub fn ty(&self) -> &FuncType {
+        &self.ty
+    }
+
+    /// The declared locals of the function, excluding its parameters
+    pub fn locals(&self) -> &[Type] {
+        &self.locals
+    }
+
+    /// The decoded body of the function; the body is reference
+    /// counted so that it can be executed while the store is mutated
+    pub fn body(&self) -> Rc<Body> {
+        Rc::clone(&self.body)
+    }
 }
 
-impl Slottable for Func {
-    fn slot() -> Slot {
-        Slot::Func
+type HostCallback = dyn Fn(Caller<'_>, &[Value]) -> Result<Vec<Value>>;
+
+/// A function implemented by the host; the callback is reference
+/// counted so that it can be invoked while the store is mutated
+#[derive(Clone)]
+pub struct HostFunc {
+    ty: FuncType,
+    callback: Rc<HostCallback>,
+}
+
+impl HostFunc {
+    pub fn new(
+        ty: FuncType,
+        callback: impl Fn(Caller<'_>, &[Value]) -> Result<Vec<Value>> + 'static,
+    ) -> Self {
+        Self {
+            ty,
+            callback: Rc::new(callback),
+        }
+    }
+
+    pub fn ty(&self) -> &FuncType {
+        &self.ty
+    }
+
+    /// Invokes the callback, checking that the values it
+    /// returns match the results of the function type
+    pub fn call(&self, caller: Caller<'_>, args: &[Value]) -> Result<Vec<Value>> {
+        let results = (self.callback)(caller, args)?;
+
+        if results.len() != self.ty.returns.len() {
+            bail!(
+                "Host function returned {} values, expected {}",
+                results.len(),
+                self.ty.returns.len()
+            );
+        }
+        for (ty, result) in self.ty.returns.iter().zip(&results) {
+            let expected = ValueType::try_from(*ty)?;
+            if expected != result.ty() {
+                bail!(
+                    "Host function result type mismatch: expected {:?}, got {:?}",
+                    expected,
+                    result.ty()
+                );
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+/// The branch targets of a structured control instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Target {
+    end: usize,
+    else_index: Option<usize>,
+}
+
+/// The instructions of a function, along with a side-table
+/// holding the branch targets of every `Block`, `Loop`, `If`
+/// and `Else` instruction, indexed by instruction index
+pub struct Body {
+    instrs: Vec<Instr>,
+    // The offset of every instruction in the module binary
+    offsets: Vec<usize>,
+    targets: Vec<Option<Target>>,
+}
+
+impl Body {
+    /// Creates a body from instructions paired with
+    /// their offsets in the module binary
+    pub fn new(instrs: Vec<(Instr, usize)>) -> Result<Self> {
+        let (instrs, offsets): (Vec<_>, Vec<_>) = instrs.into_iter().unzip();
+        let mut targets = vec![None; instrs.len()];
+        // The start index of every open structured control
+        // instruction, along with the index of its else
+        let mut open: Vec<(usize, Option<usize>)> = vec![];
+
+        for (index, instr) in instrs.iter().enumerate() {
+            match instr.kind() {
+                Kind::Block { .. } | Kind::Loop { .. } | Kind::If { .. } | Kind::Try { .. } => {
+                    open.push((index, None))
+                }
+                Kind::Else => match open.last_mut() {
+                    Some((_, else_index @ None)) => *else_index = Some(index),
+                    _ => bail!("Unexpected else instruction at {}", index),
+                },
+                Kind::End => {
+                    // The last end instruction closes the function body
+                    // which is not tracked as a structured instruction
+                    if let Some((start, else_index)) = open.pop() {
+                        targets[start] = Some(Target {
+                            end: index,
+                            else_index,
+                        });
+                        if let Some(else_index) = else_index {
+                            targets[else_index] = Some(Target {
+                                end: index,
+                                else_index: None,
+                            });
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if !open.is_empty() {
+            bail!("Unterminated structured instruction at {}", open[0].0);
+        }
+
+        Ok(Self {
+            instrs,
+            offsets,
+            targets,
+        })
+    }
+
+    pub fn instr(&self, index: usize) -> Option<&Instr> {
+        self.instrs.get(index)
+    }
+
+    /// The offset of the instruction at the given index in the module binary
+    pub fn offset(&self, index: usize) -> Option<usize> {
+        self.offsets.get(index).copied()
+    }
+
+    fn target(&self, index: usize) -> Result<Target> {
+        self.targets
+            .get(index)
+            .copied()
+            .flatten()
+            .with_context(|| format!("No branch targets for instruction {}", index))
+    }
+
+    /// The index of the end instruction matching the structured
+    /// control instruction at the given index
+    pub fn end_of(&self, index: usize) -> Result<usize> {
+        Ok(self.target(index)?.end)
+    }
+
+    /// The index of the else instruction of the if instruction
+    /// at the given index, if any
+    pub fn else_of(&self, index: usize) -> Result<Option<usize>> {
+        Ok(self.target(index)?.else_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Body;
+    use crate::{instr::Instr, module::Module};
+
+    fn body(wat: &str) -> Body {
+        let bytes = wat::parse_str(wat).unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let instrs = module.codes()[0]
+            .get_operators_reader()
+            .unwrap()
+            .into_iter_with_offsets()
+            .map(|op| {
+                let (op, offset) = op.unwrap();
+                (Instr::try_from(op).unwrap(), offset)
+            })
+            .collect();
+
+        Body::new(instrs).unwrap()
+    }
+
+    #[test]
+    fn targets() {
+        let body = body(
+            r#"(module
+                 (func
+                   block     ;; 0
+                     loop    ;; 1
+                       i32.const 0
+                       if    ;; 3
+                         nop
+                       else  ;; 5
+                         block ;; 6
+                         end   ;; 7
+                       end   ;; 8
+                     end     ;; 9
+                   end       ;; 10
+                   i32.const 1
+                   if        ;; 12
+                   end))     ;; 13"#,
+        );
+
+        assert_eq!(body.end_of(0).unwrap(), 10);
+        assert_eq!(body.end_of(1).unwrap(), 9);
+        assert_eq!(body.end_of(3).unwrap(), 8);
+        assert_eq!(body.else_of(3).unwrap(), Some(5));
+        assert_eq!(body.end_of(5).unwrap(), 8);
+        assert_eq!(body.end_of(6).unwrap(), 7);
+        assert_eq!(body.end_of(12).unwrap(), 13);
+        assert_eq!(body.else_of(12).unwrap(), None);
+        assert!(body.end_of(2).is_err());
     }
 }
@@ -13,6 +13,24 @@ impl Global {
     pub fn new(val: Value, ty: GlobalType) -> Self {
         Self { val, ty }
     }
+
+    pub fn value(&self) -> Value {
+        self.val
+    }
+
+    /// Sets the value of the global, whose mutability is checked by
+    /// the validator for wasm code and by handles for the host
+    pub fn set(&mut self, val: Value) {
+        debug_assert!(
+            self.ty.mutable,
+            "Cannot set the value of an immutable global"
+        );
+        self.val = val;
+    }
+
+    pub fn ty(&self) -> GlobalType {
+        self.ty
+    }
 }
 
 impl Slottable for Global {
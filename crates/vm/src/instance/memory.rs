@@ -0,0 +1,236 @@
+use crate::{
+    addressable::{Slot, Slottable},
+    trap::TrapCode,
+};
+use anyhow::{bail, Result};
+
+/// The size of a page of linear memory, in bytes
+pub const PAGE_SIZE: u64 = 65536;
+/// The maximum number of pages addressable by a 32-bit memory
+pub const MAX_PAGES: u64 = 65536;
+
+pub struct Memory {
+    buffer: Vec<u8>,
+    initial: u64,
+    maximum: Option<u64>,
+}
+
+impl Memory {
+    pub fn new(initial: u64, maximum: Option<u64>) -> Result<Self> {
+        if initial > MAX_PAGES {
+            bail!(
+                "Memory size of {} pages exceeds the limit of {} pages",
+                initial,
+                MAX_PAGES
+            );
+        }
+        if let Some(max) = maximum {
+            if max < initial {
+                bail!(
+                    "Memory maximum of {} pages is smaller than its initial size of {} pages",
+                    max,
+                    initial
+                );
+            }
+        }
+
+        Ok(Self {
+            buffer: vec![0; (initial * PAGE_SIZE) as usize],
+            initial,
+            maximum,
+        })
+    }
+
+    pub fn initial(&self) -> u64 {
+        self.initial
+    }
+
+    pub fn maximum(&self) -> Option<u64> {
+        self.maximum
+    }
+
+    /// The size of the memory, in pages
+    pub fn size(&self) -> u64 {
+        self.len() / PAGE_SIZE
+    }
+
+    /// The number of pages the memory can grow to
+    pub fn limit(&self) -> u64 {
+        self.maximum.unwrap_or(MAX_PAGES).min(MAX_PAGES)
+    }
+
+    /// Grows the memory by the given number of pages, returning the
+    /// previous size; fails if the maximum would be exceeded or if
+    /// the allocation fails
+    pub(crate) fn grow(&mut self, delta: u64) -> Option<u64> {
+        let size = self.size();
+        let desired = size.checked_add(delta)?;
+        if desired > self.limit() {
+            return None;
+        }
+
+        let additional = (delta * PAGE_SIZE) as usize;
+        self.buffer.try_reserve_exact(additional).ok()?;
+        self.buffer.resize(self.buffer.len() + additional, 0);
+
+        Some(size)
+    }
+
+    /// The size of the memory, in bytes
+    pub fn len(&self) -> u64 {
+        self.buffer.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Reads `N` bytes starting at the given address
+    pub fn read<const N: usize>(&self, addr: u64) -> Result<[u8; N], TrapCode> {
+        let range = self.range(addr, N as u64)?;
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&self.buffer[range]);
+
+        Ok(bytes)
+    }
+
+    /// Writes the given bytes starting at the given address;
+    /// nothing is written if the bytes don't fit in the memory
+    pub fn write(&mut self, addr: u64, bytes: &[u8]) -> Result<(), TrapCode> {
+        let range = self.range(addr, bytes.len() as u64)?;
+        self.buffer[range].copy_from_slice(bytes);
+
+        Ok(())
+    }
+
+    /// The `len` bytes starting at the given address
+    pub fn bytes(&self, addr: u64, len: u64) -> Result<&[u8], TrapCode> {
+        let range = self.range(addr, len)?;
+        Ok(&self.buffer[range])
+    }
+
+    /// Copies `len` bytes from `src` to `dst`; the regions may overlap
+    pub fn copy(&mut self, dst: u64, src: u64, len: u64) -> Result<(), TrapCode> {
+        let src = self.range(src, len)?;
+        let dst = self.range(dst, len)?;
+        self.buffer.copy_within(src, dst.start);
+
+        Ok(())
+    }
+
+    /// Sets `len` bytes starting at the given address to `val`
+    pub fn fill(&mut self, addr: u64, val: u8, len: u64) -> Result<(), TrapCode> {
+        let range = self.range(addr, len)?;
+        self.buffer[range].fill(val);
+
+        Ok(())
+    }
+
+    /// Checks that `len` bytes starting at the given address are in bounds
+    pub fn check_bounds(&self, addr: u64, len: u64) -> Result<(), TrapCode> {
+        self.range(addr, len).map(|_| ())
+    }
+
+    fn range(&self, addr: u64, len: u64) -> Result<std::ops::Range<usize>, TrapCode> {
+        match addr.checked_add(len) {
+            Some(end) if end <= self.len() => Ok(addr as usize..end as usize),
+            _ => Err(TrapCode::MemoryOutOfBounds),
+        }
+    }
+}
+
+impl Slottable for Memory {
+    fn slot() -> Slot {
+        Slot::Memory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Memory, PAGE_SIZE};
+    use crate::trap::TrapCode;
+
+    #[test]
+    fn new() {
+        let memory = Memory::new(2, Some(3)).unwrap();
+        assert_eq!(memory.len(), 2 * PAGE_SIZE);
+        assert!(Memory::new(0, None).unwrap().is_empty());
+        assert!(Memory::new(65537, None).is_err());
+        assert!(Memory::new(2, Some(1)).is_err());
+    }
+
+    #[test]
+    fn read_write() {
+        let mut memory = Memory::new(1, None).unwrap();
+        memory.write(8, &[1, 2, 3, 4]).unwrap();
+
+        assert_eq!(memory.read::<4>(8), Ok([1, 2, 3, 4]));
+        assert_eq!(memory.read::<2>(7), Ok([0, 1]));
+        assert_eq!(memory.read::<4>(PAGE_SIZE - 4), Ok([0; 4]));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut memory = Memory::new(1, None).unwrap();
+
+        assert_eq!(
+            memory.read::<4>(PAGE_SIZE - 3),
+            Err(TrapCode::MemoryOutOfBounds)
+        );
+        assert_eq!(memory.read::<1>(u64::MAX), Err(TrapCode::MemoryOutOfBounds));
+        assert_eq!(
+            memory.write(PAGE_SIZE - 1, &[1, 2]),
+            Err(TrapCode::MemoryOutOfBounds)
+        );
+        // Out of bounds writes have no side effects
+        assert_eq!(memory.read::<1>(PAGE_SIZE - 1), Ok([0]));
+    }
+
+    #[test]
+    fn grow() {
+        let mut memory = Memory::new(1, Some(3)).unwrap();
+
+        assert_eq!(memory.grow(0), Some(1));
+        assert_eq!(memory.grow(2), Some(1));
+        assert_eq!(memory.size(), 3);
+        assert_eq!(memory.read::<1>(3 * PAGE_SIZE - 1), Ok([0]));
+        assert_eq!(memory.grow(1), None);
+        assert_eq!(memory.size(), 3);
+
+        let mut memory = Memory::new(0, None).unwrap();
+        assert_eq!(memory.grow(u64::MAX), None);
+        assert_eq!(memory.grow(65537), None);
+        assert_eq!(memory.grow(1), Some(0));
+    }
+
+    #[test]
+    fn copy_fill() {
+        let mut memory = Memory::new(1, None).unwrap();
+        memory.write(0, &[1, 2, 3, 4, 5]).unwrap();
+
+        memory.copy(2, 0, 3).unwrap();
+        assert_eq!(memory.read::<5>(0), Ok([1, 2, 1, 2, 3]));
+        memory.copy(0, 1, 4).unwrap();
+        assert_eq!(memory.read::<5>(0), Ok([2, 1, 2, 3, 3]));
+
+        memory.fill(1, 0xff, 2).unwrap();
+        assert_eq!(memory.read::<4>(0), Ok([2, 0xff, 0xff, 3]));
+
+        // Out of bounds operations have no side effects
+        assert_eq!(
+            memory.copy(PAGE_SIZE - 1, 0, 2),
+            Err(TrapCode::MemoryOutOfBounds)
+        );
+        assert_eq!(
+            memory.fill(PAGE_SIZE - 1, 1, 2),
+            Err(TrapCode::MemoryOutOfBounds)
+        );
+        assert_eq!(memory.read::<1>(PAGE_SIZE - 1), Ok([0]));
+        // Empty operations at the end of the memory are in bounds
+        assert_eq!(memory.copy(PAGE_SIZE, PAGE_SIZE, 0), Ok(()));
+        assert_eq!(
+            memory.fill(PAGE_SIZE + 1, 0, 0),
+            Err(TrapCode::MemoryOutOfBounds)
+        );
+    }
+}
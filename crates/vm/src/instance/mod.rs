@@ -1,23 +1,207 @@
+pub mod data;
 pub mod elem;
 pub mod func;
 pub mod global;
+pub mod memory;
 pub mod table;
 pub use func::*;
 pub use global::*;
+pub use memory::*;
 pub use table::*;
 
+use crate::addressable::{Addr, Slot, Slottable};
+use crate::{
+    handle::{FuncHandle, GlobalHandle, MemoryHandle, TableHandle},
+    names::Names,
+    store::Store,
+    typed::{TypedFunc, WasmTypes},
+};
+use anyhow::{bail, Context, Result};
+use data::Data;
+use elem::Elem;
+use std::{collections::HashMap, rc::Rc};
 use wasmparser::FuncType;
 
 pub type Index = usize;
-// TODO(@saulecabrera): Add imports once extern values are supported
+
+/// A module instance, mapping each index space of the module
+/// to addresses in the store; imported entities come first in
+/// their index space, followed by the ones defined by the module
 #[derive(Debug, Clone)]
 pub struct Instance {
     index: Index,
     types: Vec<FuncType>,
+    funcs: Vec<Addr>,
+    tables: Vec<Addr>,
+    memories: Vec<Addr>,
+    globals: Vec<Addr>,
+    exports: HashMap<String, Addr>,
+    names: Rc<Names>,
 }
 
 impl Instance {
-    pub(crate) fn new(types: Vec<FuncType>, index: Index) -> Self {
-        Instance { index, types }
+    pub(crate) fn new(types: Vec<FuncType>, names: Rc<Names>, index: Index) -> Self {
+        Instance {
+            index,
+            types,
+            names,
+            funcs: Vec::new(),
+            tables: Vec::new(),
+            memories: Vec::new(),
+            globals: Vec::new(),
+            exports: HashMap::new(),
+        }
+    }
+
+    pub fn index(&self) -> Index {
+        self.index
+    }
+
+    pub fn types(&self) -> &[FuncType] {
+        &self.types
+    }
+
+    /// The names decoded from the name section of the module
+    pub fn names(&self) -> &Names {
+        &self.names
+    }
+
+    /// Appends an address to the index space of its slot
+    pub(crate) fn push(&mut self, addr: Addr) -> Result<()> {
+        match addr.slot() {
+            Slot::Func => self.funcs.push(addr),
+            Slot::Table => self.tables.push(addr),
+            Slot::Memory => self.memories.push(addr),
+            Slot::Global => self.globals.push(addr),
+            slot => bail!("{:?} addresses don't belong to an index space", slot),
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn push_export(&mut self, name: &str, addr: Addr) {
+        self.exports.insert(name.to_string(), addr);
+    }
+
+    /// The address of the export with the given name
+    pub fn export(&self, name: &str) -> Option<Addr> {
+        self.exports.get(name).copied()
+    }
+
+    /// Resolves an exported function
+    pub fn get_func(&self, store: &Store, name: &str) -> Result<FuncHandle> {
+        let addr = self.typed_export(name, Slot::Func)?;
+        store.func(&addr)?;
+        Ok(FuncHandle::new(addr))
+    }
+
+    /// Resolves an exported function, checking its type
+    /// against the given parameters and results
+    pub fn get_typed_func<Params: WasmTypes, Results: WasmTypes>(
+        &self,
+        store: &Store,
+        name: &str,
+    ) -> Result<TypedFunc<Params, Results>> {
+        TypedFunc::new(store, self.get_func(store, name)?)
+    }
+
+    /// Resolves an exported global
+    pub fn get_global(&self, store: &Store, name: &str) -> Result<GlobalHandle> {
+        let addr = self.typed_export(name, Slot::Global)?;
+        store.global(&addr)?;
+        Ok(GlobalHandle::new(addr))
+    }
+
+    /// Resolves an exported table
+    pub fn get_table(&self, store: &Store, name: &str) -> Result<TableHandle> {
+        let addr = self.typed_export(name, Slot::Table)?;
+        store.table(&addr)?;
+        Ok(TableHandle::new(addr))
+    }
+
+    /// Resolves an exported memory
+    pub fn get_memory(&self, store: &Store, name: &str) -> Result<MemoryHandle> {
+        let addr = self.typed_export(name, Slot::Memory)?;
+        store.memory(&addr)?;
+        Ok(MemoryHandle::new(addr))
+    }
+
+    fn typed_export(&self, name: &str, slot: Slot) -> Result<Addr> {
+        let addr = self
+            .export(name)
+            .with_context(|| format!("Unknown export {}", name))?;
+        if addr.slot() != slot {
+            bail!(
+                "Export {} is a {:?}, expected a {:?}",
+                name,
+                addr.slot(),
+                slot
+            );
+        }
+
+        Ok(addr)
+    }
+
+    /// The number of entries in the index space of the given slot
+    ///
+    /// NB
+    /// Segments can't be imported, so they have no index
+    /// space and their addresses are derived from their index
+    pub(crate) fn len(&self, slot: Slot) -> u32 {
+        let len = match slot {
+            Slot::Func => self.funcs.len(),
+            Slot::Table => self.tables.len(),
+            Slot::Memory => self.memories.len(),
+            Slot::Global => self.globals.len(),
+            Slot::Elem | Slot::Data => 0,
+        };
+
+        len as u32
+    }
+
+    /// Resolves the address of a function in the
+    /// function index space of the instance
+    pub fn func_addr(&self, index: u32) -> Result<Addr> {
+        self.funcs
+            .get(index as usize)
+            .copied()
+            .with_context(|| format!("Invalid function index {}", index))
+    }
+
+    /// Resolves the address of a global in the
+    /// global index space of the instance
+    pub fn global_addr(&self, index: u32) -> Result<Addr> {
+        self.globals
+            .get(index as usize)
+            .copied()
+            .with_context(|| format!("Invalid global index {}", index))
+    }
+
+    /// Resolves the address of a table in the
+    /// table index space of the instance
+    pub fn table_addr(&self, index: u32) -> Result<Addr> {
+        self.tables
+            .get(index as usize)
+            .copied()
+            .with_context(|| format!("Invalid table index {}", index))
+    }
+
+    /// Resolves the address of a memory in the
+    /// memory index space of the instance
+    pub fn memory_addr(&self, index: u32) -> Result<Addr> {
+        self.memories
+            .get(index as usize)
+            .copied()
+            .with_context(|| format!("Invalid memory index {}", index))
+    }
+
+    /// Resolves the address of an element segment of the instance
+    pub fn elem_addr(&self, index: u32) -> Addr {
+        Addr::new_unsafe(self.index, index, Elem::slot())
+    }
+
+    /// Resolves the address of a data segment of the instance
+    pub fn data_addr(&self, index: u32) -> Addr {
+        Addr::new_unsafe(self.index, index, Data::slot())
     }
 }
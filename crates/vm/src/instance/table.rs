@@ -1,9 +1,18 @@
 use crate::{
     addressable::{Slot, Slottable},
+    trap::TrapCode,
     val::{RefType, RefValue, Value},
 };
 use anyhow::{bail, Result};
 
+/// The maximum number of elements a table can grow to
+///
+/// NB
+/// The spec allows up to 2^32 - 1 elements, but growing
+/// is allowed to fail; the limit prevents hosts from
+/// being exhausted by a single `table.grow`
+pub const MAX_TABLE_SIZE: u32 = 10_000_000;
+
 pub struct Table {
     buffer: Vec<RefValue>,
     ty: RefType,
@@ -14,50 +23,123 @@ pub struct Table {
 impl Table {
     pub fn new(ty: RefType, initial: u32, maximum: Option<u32>) -> Result<Self> {
         Ok(Self {
-            buffer: std::iter::repeat(RefValue::Null(ty))
-                .take(initial as usize)
-                .collect(),
+            buffer: vec![RefValue::Null(ty); initial as usize],
             ty,
             initial,
             maximum,
         })
     }
 
-    fn validate_offset(&self, offset: usize) -> Result<()> {
-        let size = self.buffer.len();
-        let out_of_bounds = || {
-            bail!(
-                "Table out of bounds, tried to access {}, but table has length {}",
-                offset,
-                size
-            )
-        };
-        match offset.checked_add(size) {
-            Some(addr) => {
-                if addr <= size {
-                    return Ok(());
-                }
-                out_of_bounds()
-            }
-            None => out_of_bounds(),
-        }
+    pub fn ty(&self) -> RefType {
+        self.ty
+    }
+
+    pub fn initial(&self) -> u32 {
+        self.initial
+    }
+
+    pub fn maximum(&self) -> Option<u32> {
+        self.maximum
+    }
+
+    /// The number of elements in the table
+    pub fn size(&self) -> u32 {
+        self.buffer.len() as u32
+    }
+
+    /// The number of elements the table can grow to
+    pub fn limit(&self) -> u32 {
+        self.maximum.unwrap_or(MAX_TABLE_SIZE).min(MAX_TABLE_SIZE)
+    }
+
+    pub fn get(&self, index: u32) -> Option<RefValue> {
+        self.buffer.get(index as usize).copied()
     }
 
-    pub fn init(&mut self, val: &Value, data: Vec<RefValue>) -> Result<()> {
-        let offset = match val {
-            Value::I32(v) => usize::try_from(*v),
-            Value::I64(v) => usize::try_from(*v),
-            v => bail!("Unexpected value in table initialization: {:?}, expected ValueType::I32 or ValueType::I64", v),
-        }?;
+    pub fn set(&mut self, index: u32, val: RefValue) -> Result<(), TrapCode> {
+        let elem = self
+            .buffer
+            .get_mut(index as usize)
+            .ok_or(TrapCode::TableOutOfBounds)?;
+        *elem = val;
 
-        self.validate_offset(offset)?;
+        Ok(())
+    }
 
-        for (index, ref_val) in data.iter().enumerate() {
-            self.buffer[offset + index] = *ref_val;
+    /// Grows the table by the given number of elements, all set to
+    /// `init`, returning the previous size; fails if the maximum
+    /// would be exceeded or if the allocation fails
+    pub(crate) fn grow(&mut self, delta: u32, init: RefValue) -> Option<u32> {
+        let size = self.size();
+        let desired = size.checked_add(delta)?;
+        if desired > self.limit() {
+            return None;
         }
 
+        self.buffer.try_reserve_exact(delta as usize).ok()?;
+        self.buffer.resize(desired as usize, init);
+
+        Some(size)
+    }
+
+    /// Initializes the table with the elements of an active segment;
+    /// nothing is written if the elements don't fit in the table
+    pub fn init(&mut self, offset: &Value, elems: &[RefValue]) -> Result<()> {
+        let offset = match offset {
+            Value::I32(v) => *v as u32,
+            v => bail!(
+                "Unexpected value in table initialization: {:?}, expected ValueType::I32",
+                v
+            ),
+        };
+
+        Ok(self.write(offset, elems)?)
+    }
+
+    /// The `len` elements starting at the given index
+    pub fn elements(&self, index: u32, len: u32) -> Result<&[RefValue], TrapCode> {
+        let range = self.range(index, len)?;
+        Ok(&self.buffer[range])
+    }
+
+    /// Writes the given elements starting at the given index;
+    /// nothing is written if the elements don't fit in the table
+    pub fn write(&mut self, index: u32, elems: &[RefValue]) -> Result<(), TrapCode> {
+        let len = u32::try_from(elems.len()).map_err(|_| TrapCode::TableOutOfBounds)?;
+        let range = self.range(index, len)?;
+        self.buffer[range].copy_from_slice(elems);
+
+        Ok(())
+    }
+
+    /// Sets `len` elements starting at the given index to `val`
+    pub fn fill(&mut self, index: u32, val: RefValue, len: u32) -> Result<(), TrapCode> {
+        let range = self.range(index, len)?;
+        self.buffer[range].fill(val);
+
+        Ok(())
+    }
+
+    /// Checks that `len` elements starting at the given index are in bounds
+    pub fn check_bounds(&self, index: u32, len: u32) -> Result<(), TrapCode> {
+        self.range(index, len).map(|_| ())
+    }
+
+    /// Copies `len` elements from `src` to `dst`; the regions may overlap
+    pub fn copy(&mut self, dst: u32, src: u32, len: u32) -> Result<(), TrapCode> {
+        let src = self.range(src, len)?;
+        let dst = self.range(dst, len)?;
+        self.buffer.copy_within(src, dst.start);
+
         Ok(())
     }
+
+    fn range(&self, index: u32, len: u32) -> Result<std::ops::Range<usize>, TrapCode> {
+        match index.checked_add(len) {
+            Some(end) if end <= self.size() => Ok(index as usize..end as usize),
+            _ => Err(TrapCode::TableOutOfBounds),
+        }
+    }
 }
 
 impl Slottable for Table {
@@ -65,3 +147,120 @@ impl Slottable for Table {
         Slot::Table
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Table, MAX_TABLE_SIZE};
+    use crate::{
+        addressable::{Addr, Slot},
+        trap::TrapCode,
+        val::{RefType, RefValue, Value},
+    };
+
+    fn func_ref(index: u32) -> RefValue {
+        RefValue::FuncRef(Addr::new_unsafe(0, index, Slot::Func))
+    }
+
+    const NULL: RefValue = RefValue::Null(RefType::FuncRef);
+
+    #[test]
+    fn get_set() {
+        let mut table = Table::new(RefType::FuncRef, 2, None).unwrap();
+        table.set(1, func_ref(0)).unwrap();
+
+        assert_eq!(table.get(0), Some(NULL));
+        assert_eq!(table.get(1), Some(func_ref(0)));
+        assert_eq!(table.get(2), None);
+        assert_eq!(table.set(2, func_ref(0)), Err(TrapCode::TableOutOfBounds));
+    }
+
+    #[test]
+    fn grow() {
+        let mut table = Table::new(RefType::FuncRef, 1, Some(3)).unwrap();
+
+        assert_eq!(table.grow(0, NULL), Some(1));
+        assert_eq!(table.grow(2, func_ref(1)), Some(1));
+        assert_eq!(table.size(), 3);
+        assert_eq!(table.get(2), Some(func_ref(1)));
+        assert_eq!(table.grow(1, NULL), None);
+
+        let mut table = Table::new(RefType::FuncRef, 0, None).unwrap();
+        assert_eq!(table.grow(u32::MAX, NULL), None);
+        assert_eq!(table.grow(MAX_TABLE_SIZE + 1, NULL), None);
+        assert_eq!(table.grow(1, NULL), Some(0));
+    }
+
+    #[test]
+    fn fill_copy() {
+        let mut table = Table::new(RefType::FuncRef, 4, None).unwrap();
+        table.write(0, &[func_ref(0), func_ref(1)]).unwrap();
+
+        table.copy(1, 0, 2).unwrap();
+        assert_eq!(
+            table.elements(0, 3),
+            Ok(&[func_ref(0), func_ref(0), func_ref(1)][..])
+        );
+
+        table.fill(2, NULL, 2).unwrap();
+        assert_eq!(table.elements(1, 3), Ok(&[func_ref(0), NULL, NULL][..]));
+
+        // Out of bounds operations have no side effects
+        assert_eq!(
+            table.fill(3, func_ref(2), 2),
+            Err(TrapCode::TableOutOfBounds)
+        );
+        assert_eq!(table.copy(0, 3, 2), Err(TrapCode::TableOutOfBounds));
+        assert_eq!(table.elements(0, 4).unwrap()[3], NULL);
+        // Empty operations at the end of the table are in bounds
+        assert_eq!(table.fill(4, NULL, 0), Ok(()));
+        assert_eq!(table.copy(5, 0, 0), Err(TrapCode::TableOutOfBounds));
+    }
+
+    #[test]
+    fn init() {
+        let mut table = Table::new(RefType::FuncRef, 3, None).unwrap();
+
+        table
+            .init(&Value::I32(1), &[func_ref(0), func_ref(1)])
+            .unwrap();
+        assert_eq!(
+            table.elements(0, 3),
+            Ok(&[NULL, func_ref(0), func_ref(1)][..])
+        );
+        // Empty segments can be written right at the end of the table
+        assert!(table.init(&Value::I32(3), &[]).is_ok());
+    }
+
+    #[test]
+    fn init_out_of_bounds() {
+        let mut table = Table::new(RefType::FuncRef, 3, None).unwrap();
+        let trap = |result: anyhow::Result<()>| result.unwrap_err().downcast::<TrapCode>().unwrap();
+
+        assert_eq!(
+            trap(table.init(&Value::I32(2), &[func_ref(0), func_ref(1)])),
+            TrapCode::TableOutOfBounds
+        );
+        assert_eq!(
+            trap(table.init(&Value::I32(4), &[])),
+            TrapCode::TableOutOfBounds
+        );
+        assert_eq!(
+            trap(table.init(&Value::I32(-1), &[func_ref(0)])),
+            TrapCode::TableOutOfBounds
+        );
+        assert_eq!(
+            trap(table.init(&Value::I32(i32::MIN), &[])),
+            TrapCode::TableOutOfBounds
+        );
+        // Out of bounds segments have no side effects
+        assert_eq!(table.elements(0, 3), Ok(&[NULL; 3][..]));
+
+        let mut table = Table::new(RefType::FuncRef, 0, None).unwrap();
+        assert!(table.init(&Value::I32(0), &[]).is_ok());
+        assert_eq!(
+            trap(table.init(&Value::I32(0), &[func_ref(0)])),
+            TrapCode::TableOutOfBounds
+        );
+        assert!(table.init(&Value::I64(0), &[]).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::val::{RefValue, Value};
+use crate::val::Value;
 use std::convert::TryFrom;
 use wasmparser::{
     BinaryReaderError, Ieee32, Ieee64, MemoryImmediate, Operator, Operator::*, SIMDLaneIndex, Type,
@@ -11,6 +11,10 @@ pub struct Instr {
 }
 
 impl Instr {
+    pub fn kind(&self) -> &Kind {
+        &self.kind
+    }
+
     pub fn const_value(&self) -> Option<Value> {
         match self.kind {
             Kind::I32Const { value } => Some(Value::I32(value)),
@@ -37,6 +41,17 @@ pub struct BrTableData {
     default: u32,
 }
 
+impl BrTableData {
+    /// Resolves the relative depth of the branch for a given
+    /// operand; out of range operands select the default target
+    pub fn target(&self, index: u32) -> u32 {
+        self.targets
+            .get(index as usize)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
 fn convert_table(table: wasmparser::BrTable) -> Result<BrTableData, BinaryReaderError> {
     Ok(BrTableData {
         targets: table.targets().collect::<Result<Vec<_>, _>>()?,
@@ -1,6 +1,13 @@
+// The arity of a label is the number of values
+// that a branch to the label carries
 pub enum Label {
-    If(usize),
-    Block(usize),
+    // (arity, continuation)
+    // The continuation of a block or an if is the
+    // index of the instruction following its end
+    If(usize, usize),
+    Block(usize, usize),
+    // The body of a function, whose arity
+    // is given by the function's frame
     Ret,
     // (arity, continuation)
     // The continuation of a loop is a given instruction index;
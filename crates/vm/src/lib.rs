@@ -1,11 +1,20 @@
 pub mod addressable;
+pub mod caller;
+pub mod config;
 pub mod frame;
-pub mod func;
+pub mod fuel;
+pub mod handle;
 pub mod instance;
 pub mod instr;
 pub mod label;
+pub mod limiter;
+pub mod linker;
 pub mod module;
+pub mod names;
+pub mod num;
 pub mod stack;
 pub mod store;
+pub mod trap;
+pub mod typed;
 pub mod val;
 pub mod vm;
@@ -0,0 +1,32 @@
+use crate::addressable::Addr;
+
+/// Host hooks consulted by the store before a memory or a table grows.
+///
+/// The hooks are only consulted for requests that are valid with respect
+/// to the type of the memory or the table; denying a request makes the
+/// growing instruction fail as if the maximum had been reached.
+///
+/// Memories and tables defined by a module are also subject to the hooks
+/// when they are created, growing from zero to their initial size; denying
+/// the creation makes the instantiation fail.
+pub trait ResourceLimiter {
+    /// Whether the memory at the given address is allowed to grow
+    /// from `current` to `desired` pages
+    fn memory_growing(
+        &mut self,
+        addr: Addr,
+        current: u64,
+        desired: u64,
+        maximum: Option<u64>,
+    ) -> bool;
+
+    /// Whether the table at the given address is allowed to grow
+    /// from `current` to `desired` elements
+    fn table_growing(
+        &mut self,
+        addr: Addr,
+        current: u32,
+        desired: u32,
+        maximum: Option<u32>,
+    ) -> bool;
+}
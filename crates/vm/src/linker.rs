@@ -0,0 +1,607 @@
+use crate::{
+    addressable::{Addr, Slot},
+    caller::Caller,
+    instance::{HostFunc, Instance},
+    module::Module,
+    store::Store,
+    typed::WasmTypes,
+    val::{RefType, Value},
+};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use wasmparser::{FuncType, Import, ImportSectionEntryType, Type};
+
+/// A value provided to satisfy an import
+pub(crate) enum Extern {
+    HostFunc(HostFunc),
+    /// An entity exported by another instance of the store
+    Export(Addr),
+}
+
+/// Resolves the imports of a module by `module.name`, first against
+/// the definitions registered by the host and then against the exports
+/// of the instances registered in the store under the name `module`
+#[derive(Default)]
+pub struct Linker {
+    funcs: HashMap<(String, String), HostFunc>,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines a host function, which can be imported
+    /// by modules as `module.name` with the given type
+    pub fn func(
+        &mut self,
+        module: &str,
+        name: &str,
+        ty: FuncType,
+        func: impl Fn(Caller<'_>, &[Value]) -> Result<Vec<Value>> + 'static,
+    ) -> Result<&mut Self> {
+        let key = (module.to_string(), name.to_string());
+        if self.funcs.contains_key(&key) {
+            bail!("Import {}.{} is already defined", module, name);
+        }
+        self.funcs.insert(key, HostFunc::new(ty, func));
+
+        Ok(self)
+    }
+
+    /// Defines a host function whose type is derived from the Rust
+    /// types of its parameters and results
+    pub fn typed_func<Params: WasmTypes, Results: WasmTypes>(
+        &mut self,
+        module: &str,
+        name: &str,
+        func: impl Fn(Caller<'_>, Params) -> Result<Results> + 'static,
+    ) -> Result<&mut Self> {
+        let ty = FuncType {
+            params: Params::types().into_iter().map(Type::from).collect(),
+            returns: Results::types().into_iter().map(Type::from).collect(),
+        };
+
+        self.func(module, name, ty, move |caller, args| {
+            let params = Params::from_values(args.to_vec())?;
+            Ok(func(caller, params)?.into_values())
+        })
+    }
+
+    /// Instantiates a module, resolving its imports; fails
+    /// listing every import that couldn't be resolved
+    pub fn instantiate(
+        &self,
+        store: &mut Store,
+        module: &Module,
+        name: Option<String>,
+    ) -> Result<Instance> {
+        let types = module.func_types();
+        let mut imports = Vec::with_capacity(module.imports().len());
+        let mut unresolved = Vec::new();
+
+        for import in module.imports() {
+            match self.resolve(store, &types, import)? {
+                Some(import) => imports.push(import),
+                None => unresolved.push(import_name(import)),
+            }
+        }
+
+        if !unresolved.is_empty() {
+            bail!("Unresolved imports: {}", unresolved.join(", "));
+        }
+
+        store.instantiate_with_imports(module, name, imports)
+    }
+
+    fn resolve(
+        &self,
+        store: &Store,
+        types: &[FuncType],
+        import: &Import,
+    ) -> Result<Option<Extern>> {
+        let field = import.field.unwrap_or_default();
+
+        if let Some(func) = self
+            .funcs
+            .get(&(import.module.to_string(), field.to_string()))
+        {
+            match import.ty {
+                ImportSectionEntryType::Function(index) => {
+                    check_func_type(import, func_type(types, index)?, func.ty())?
+                }
+                ty => bail!(
+                    "Incompatible import type for {}: expected {:?}, found a host function",
+                    import_name(import),
+                    ty
+                ),
+            }
+
+            return Ok(Some(Extern::HostFunc(func.clone())));
+        }
+
+        let addr = match store
+            .instance_by_name(import.module)
+            .and_then(|instance| instance.export(field))
+        {
+            Some(addr) => addr,
+            None => return Ok(None),
+        };
+        check_import_type(store, types, import, addr)?;
+
+        Ok(Some(Extern::Export(addr)))
+    }
+}
+
+fn import_name(import: &Import) -> String {
+    format!("{}.{}", import.module, import.field.unwrap_or_default())
+}
+
+fn func_type(types: &[FuncType], index: u32) -> Result<&FuncType> {
+    types
+        .get(index as usize)
+        .with_context(|| format!("Invalid type index {}", index))
+}
+
+fn check_func_type(import: &Import, expected: &FuncType, actual: &FuncType) -> Result<()> {
+    if expected != actual {
+        bail!(
+            "Incompatible import type for {}: expected {:?}, got {:?}",
+            import_name(import),
+            expected,
+            actual
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks the type of an export against the type of the import it satisfies
+fn check_import_type(store: &Store, types: &[FuncType], import: &Import, addr: Addr) -> Result<()> {
+    let incompatible = || format!("Incompatible import type for {}", import_name(import));
+
+    match (import.ty, addr.slot()) {
+        (ImportSectionEntryType::Function(index), Slot::Func) => {
+            check_func_type(import, func_type(types, index)?, store.func(&addr)?.ty())
+        }
+        (ImportSectionEntryType::Global(expected), Slot::Global) => {
+            let actual = store.global(&addr)?.ty();
+            if expected != actual {
+                bail!(
+                    "{}: expected {:?}, got {:?}",
+                    incompatible(),
+                    expected,
+                    actual
+                );
+            }
+            Ok(())
+        }
+        (ImportSectionEntryType::Table(expected), Slot::Table) => {
+            let table = store.table(&addr)?;
+            if RefType::try_from(expected.element_type)? != table.ty() {
+                bail!(
+                    "{}: expected {:?} elements, got {:?}",
+                    incompatible(),
+                    expected.element_type,
+                    table.ty()
+                );
+            }
+            check_limits(
+                (table.size() as u64, table.maximum().map(u64::from)),
+                (expected.initial as u64, expected.maximum.map(u64::from)),
+            )
+            .with_context(incompatible)
+        }
+        (ImportSectionEntryType::Memory(expected), Slot::Memory) => {
+            let memory = store.memory(&addr)?;
+            if expected.memory64 {
+                bail!("{}: 64-bit memories are not supported", incompatible());
+            }
+            check_limits(
+                (memory.size(), memory.maximum()),
+                (expected.initial, expected.maximum),
+            )
+            .with_context(incompatible)
+        }
+        (expected, slot) => bail!(
+            "{}: expected {:?}, found a {:?}",
+            incompatible(),
+            expected,
+            slot
+        ),
+    }
+}
+
+/// Checks that the current size and maximum of an export
+/// match the limits declared by an import
+fn check_limits(actual: (u64, Option<u64>), expected: (u64, Option<u64>)) -> Result<()> {
+    let (size, maximum) = actual;
+    let (initial, expected_maximum) = expected;
+
+    if size < initial {
+        bail!("Size {} is smaller than the minimum of {}", size, initial);
+    }
+    if let Some(expected_maximum) = expected_maximum {
+        match maximum {
+            Some(maximum) if maximum <= expected_maximum => (),
+            _ => bail!(
+                "Maximum {:?} exceeds the maximum of {}",
+                maximum,
+                expected_maximum
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Linker;
+    use crate::{
+        addressable::{Addr, Slot},
+        module::Module,
+        store::Store,
+        trap::{Trap, TrapCode},
+        val::Value,
+        vm::VM,
+    };
+    use anyhow::{bail, Result};
+    use std::{cell::RefCell, error::Error, rc::Rc};
+    use wasmparser::{FuncType, Type};
+
+    fn func_type(params: &[Type], returns: &[Type]) -> FuncType {
+        FuncType {
+            params: params.to_vec().into_boxed_slice(),
+            returns: returns.to_vec().into_boxed_slice(),
+        }
+    }
+
+    fn run(linker: &Linker, wat: &str, index: u32, args: &[Value]) -> Result<Vec<Value>> {
+        let bytes = wat::parse_str(wat)?;
+        let module = Module::from_binary(&bytes)?;
+        let mut store = Store::new();
+        let instance = linker.instantiate(&mut store, &module, None)?;
+        let addr = Addr::new_unsafe(instance.index(), index, Slot::Func);
+
+        VM::new().invoke(&mut store, addr, args)
+    }
+
+    #[test]
+    fn host_funcs() {
+        let mut linker = Linker::new();
+        linker
+            .func(
+                "env",
+                "add",
+                func_type(&[Type::I32, Type::I32], &[Type::I32]),
+                |_, args| match args {
+                    [Value::I32(a), Value::I32(b)] => Ok(vec![Value::I32(a + b)]),
+                    _ => bail!("Unexpected arguments {:?}", args),
+                },
+            )
+            .unwrap();
+
+        let wat = r#"(module
+                       (import "env" "add" (func $add (param i32 i32) (result i32)))
+                       (func (param i32) (result i32)
+                         local.get 0
+                         i32.const 40
+                         call $add))"#;
+
+        assert_eq!(
+            run(&linker, wat, 1, &[Value::I32(2)]).unwrap(),
+            vec![Value::I32(42)]
+        );
+        // Host functions can be invoked directly
+        assert_eq!(
+            run(&linker, wat, 0, &[Value::I32(1), Value::I32(2)]).unwrap(),
+            vec![Value::I32(3)]
+        );
+    }
+
+    #[test]
+    fn caller_memory() {
+        let logged = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&logged);
+
+        let mut linker = Linker::new();
+        linker
+            .func(
+                "env",
+                "log",
+                func_type(&[Type::I32, Type::I32], &[]),
+                move |caller, args| {
+                    if let [Value::I32(ptr), Value::I32(len)] = args {
+                        let bytes = caller.memory(0)?.bytes(*ptr as u64, *len as u64)?;
+                        log.borrow_mut().push(String::from_utf8(bytes.to_vec())?);
+                    }
+                    Ok(vec![])
+                },
+            )
+            .unwrap();
+
+        let wat = r#"(module
+                       (import "env" "log" (func $log (param i32 i32)))
+                       (memory 1)
+                       (data (i32.const 8) "hello")
+                       (func
+                         i32.const 8
+                         i32.const 5
+                         call $log))"#;
+
+        run(&linker, wat, 1, &[]).unwrap();
+        assert_eq!(*logged.borrow(), vec!["hello".to_string()]);
+    }
+
+    #[test]
+    fn host_errors() {
+        let mut linker = Linker::new();
+        linker
+            .func("env", "fail", func_type(&[], &[Type::I32]), |_, _| {
+                bail!("host failure")
+            })
+            .unwrap()
+            .func("env", "wrong", func_type(&[], &[Type::I32]), |_, _| {
+                Ok(vec![Value::I64(0)])
+            })
+            .unwrap();
+        assert!(linker
+            .func("env", "fail", func_type(&[], &[]), |_, _| Ok(vec![]))
+            .is_err());
+
+        let wat = r#"(module
+                       (import "env" "fail" (func $fail (result i32)))
+                       (import "env" "wrong" (func $wrong (result i32)))
+                       (func (result i32) call $fail)
+                       (func (result i32) call $wrong))"#;
+
+        let trap = |index| {
+            run(&linker, wat, index, &[])
+                .unwrap_err()
+                .downcast::<Trap>()
+                .unwrap()
+        };
+        let err = trap(2);
+        assert_eq!(err.code(), TrapCode::HostError);
+        assert_eq!(err.source().unwrap().to_string(), "host failure");
+        assert_eq!(err.backtrace().len(), 1);
+        let err = trap(3);
+        assert!(err
+            .source()
+            .unwrap()
+            .to_string()
+            .starts_with("Host function result type mismatch"));
+    }
+
+    #[test]
+    fn unresolved_imports() {
+        let mut linker = Linker::new();
+        linker
+            .func("env", "f", func_type(&[Type::I32], &[]), |_, _| Ok(vec![]))
+            .unwrap();
+
+        let unknown = r#"(module (import "env" "g" (func)))"#;
+        let err = run(&linker, unknown, 0, &[]).unwrap_err();
+        assert_eq!(err.to_string(), "Unresolved imports: env.g");
+
+        let mismatch = r#"(module (import "env" "f" (func (param i64))))"#;
+        let err = run(&linker, mismatch, 0, &[]).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Incompatible import type for env.f"));
+
+        // Modules with imports can't be instantiated without a linker
+        let bytes = wat::parse_str(r#"(module (import "env" "f" (func (param i32))))"#).unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        assert!(Store::new().instantiate(&module, None).is_err());
+    }
+
+    #[test]
+    fn instance_imports() {
+        let libc = wat::parse_str(
+            r#"(module
+                 (global (export "errno") (mut i32) (i32.const 0))
+                 (table (export "table") 1 funcref)
+                 (memory (export "memory") 1 2)
+                 (func (export "strlen") (param i32) (result i32)
+                   (local i32)
+                   (block
+                     (loop
+                       local.get 0
+                       local.get 1
+                       i32.add
+                       i32.load8_u
+                       i32.eqz
+                       br_if 1
+                       local.get 1
+                       i32.const 1
+                       i32.add
+                       local.set 1
+                       br 0))
+                   local.get 1))"#,
+        )
+        .unwrap();
+        let app = wat::parse_str(
+            r#"(module
+                 (import "libc" "strlen" (func $strlen (param i32) (result i32)))
+                 (import "libc" "errno" (global $errno (mut i32)))
+                 (import "libc" "memory" (memory 1))
+                 (import "libc" "table" (table 1 funcref))
+                 (data (i32.const 16) "hello")
+                 (elem (i32.const 0) func $strlen)
+                 (func (export "main") (result i32)
+                   i32.const 7
+                   global.set $errno
+                   i32.const 16
+                   i32.const 0
+                   call_indirect (param i32) (result i32)))"#,
+        )
+        .unwrap();
+
+        let linker = Linker::new();
+        let mut store = Store::new();
+        let libc = linker
+            .instantiate(
+                &mut store,
+                &Module::from_binary(&libc).unwrap(),
+                Some("libc".to_string()),
+            )
+            .unwrap();
+        let app = linker
+            .instantiate(&mut store, &Module::from_binary(&app).unwrap(), None)
+            .unwrap();
+
+        let main = app.get_func(&store, "main").unwrap();
+        assert_eq!(main.call(&mut store, &[]).unwrap(), vec![Value::I32(5)]);
+
+        let errno = libc.get_global(&store, "errno").unwrap();
+        assert_eq!(errno.get(&store).unwrap(), Value::I32(7));
+    }
+
+    const LIB: &str = r#"(module
+                           (type $t (func (result i32)))
+                           (table (export "table") 1 funcref)
+                           (memory (export "memory") 1)
+                           (func (export "call0") (result i32)
+                             (call_indirect (type $t) (i32.const 0))))"#;
+
+    #[test]
+    fn failed_instantiation_keeps_funcs() {
+        let lib = wat::parse_str(LIB).unwrap();
+        let failing = wat::parse_str(
+            r#"(module
+                 (import "lib" "table" (table 1 funcref))
+                 (func $f (result i32) i32.const 111)
+                 (func $start unreachable)
+                 (elem (i32.const 0) func $f)
+                 (start $start))"#,
+        )
+        .unwrap();
+        let other = wat::parse_str(r#"(module (func (result i32) i32.const 222))"#).unwrap();
+
+        let linker = Linker::new();
+        let mut store = Store::new();
+        let lib = linker
+            .instantiate(
+                &mut store,
+                &Module::from_binary(&lib).unwrap(),
+                Some("lib".to_string()),
+            )
+            .unwrap();
+        let call0 = lib.get_func(&store, "call0").unwrap();
+
+        assert!(linker
+            .instantiate(&mut store, &Module::from_binary(&failing).unwrap(), None)
+            .is_err());
+        linker
+            .instantiate(&mut store, &Module::from_binary(&other).unwrap(), None)
+            .unwrap();
+        assert_eq!(call0.call(&mut store, &[]).unwrap(), vec![Value::I32(111)]);
+    }
+
+    #[test]
+    fn failed_segments_leave_imports_untouched() {
+        let lib = wat::parse_str(LIB).unwrap();
+        let failing_elems = wat::parse_str(
+            r#"(module
+                 (import "lib" "table" (table 1 funcref))
+                 (func $f (result i32) i32.const 111)
+                 (elem (i32.const 0) func $f)
+                 (elem (i32.const 1) func $f))"#,
+        )
+        .unwrap();
+        let failing_datas = wat::parse_str(
+            r#"(module
+                 (import "lib" "table" (table 1 funcref))
+                 (import "lib" "memory" (memory 1))
+                 (func $f (result i32) i32.const 111)
+                 (elem (i32.const 0) func $f)
+                 (data (i32.const 0) "\2a")
+                 (data (i32.const 65536) "\2a"))"#,
+        )
+        .unwrap();
+
+        for failing in [failing_elems, failing_datas] {
+            let linker = Linker::new();
+            let mut store = Store::new();
+            let lib = linker
+                .instantiate(
+                    &mut store,
+                    &Module::from_binary(&lib).unwrap(),
+                    Some("lib".to_string()),
+                )
+                .unwrap();
+
+            let err = linker
+                .instantiate(&mut store, &Module::from_binary(&failing).unwrap(), None)
+                .unwrap_err();
+            assert!(err.is::<Trap>());
+            let call0 = lib.get_func(&store, "call0").unwrap();
+            let err = call0.call(&mut store, &[]).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<Trap>().map(Trap::code),
+                Some(TrapCode::NullReference)
+            );
+            let memory = lib.get_memory(&store, "memory").unwrap();
+            assert_eq!(memory.memory(&store).unwrap().read::<1>(0), Ok([0]));
+        }
+    }
+
+    #[test]
+    fn instance_import_types() {
+        let linker = Linker::new();
+        let mut store = Store::new();
+        let libc = wat::parse_str(
+            r#"(module
+                 (global (export "errno") (mut i32) (i32.const 0))
+                 (memory (export "memory") 1 2)
+                 (func (export "f") (param i32)))"#,
+        )
+        .unwrap();
+        linker
+            .instantiate(
+                &mut store,
+                &Module::from_binary(&libc).unwrap(),
+                Some("libc".to_string()),
+            )
+            .unwrap();
+
+        let mut instantiate = |wat: &str| {
+            let bytes = wat::parse_str(wat).unwrap();
+            let module = Module::from_binary(&bytes).unwrap();
+            linker
+                .instantiate(&mut store, &module, None)
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            instantiate(
+                r#"(module
+                     (import "libc" "missing" (func))
+                     (import "libc" "f" (func (param i32)))
+                     (import "other" "g" (global i32)))"#
+            ),
+            "Unresolved imports: libc.missing, other.g"
+        );
+        assert!(
+            instantiate(r#"(module (import "libc" "f" (func (param i64))))"#)
+                .starts_with("Incompatible import type for libc.f")
+        );
+        assert!(
+            instantiate(r#"(module (import "libc" "errno" (global i32)))"#)
+                .starts_with("Incompatible import type for libc.errno")
+        );
+        assert!(
+            instantiate(r#"(module (import "libc" "memory" (memory 2)))"#)
+                .starts_with("Incompatible import type for libc.memory")
+        );
+        assert!(
+            instantiate(r#"(module (import "libc" "memory" (memory 1 1)))"#)
+                .starts_with("Incompatible import type for libc.memory")
+        );
+        assert!(instantiate(r#"(module (import "libc" "memory" (func)))"#)
+            .starts_with("Incompatible import type for libc.memory"));
+    }
+}
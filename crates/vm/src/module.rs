@@ -1,8 +1,14 @@
-use anyhow::{bail, Result};
+use crate::{config::Config, names::Names};
+use anyhow::{anyhow, bail, Result};
+use std::{fmt, rc::Rc};
 use wasmparser::*;
 
 pub type Index = u32;
 
+// NB
+// Custom sections are recorded as-is; only the name
+// section is interpreted, as it's parsed
+#[allow(dead_code)]
 struct CustomSection<'a> {
     name: &'a str,
     data_offset: usize,
@@ -11,32 +17,42 @@ struct CustomSection<'a> {
 }
 
 pub struct Module<'a> {
-    // NB
-    // The custom name section can be used to obtain
-    // function names. `NameSectionReader` can be used
-    // to retrieve function names and `ExportSectionReader`
-    // can be used to retrieve export names.
-    // Ref: https://github.com/bytecodealliance/wasm-tools/issues/104
-    //
     start_fn_idx: Option<Index>,
+    // The proposals the module was validated against
+    config: Config,
+    // Shared with the instances of the module
+    names: Rc<Names>,
 
     types: Vec<FuncType>,
     imports: Vec<Import<'a>>,
-    pub functions: Vec<Index>,
-    pub tables: Vec<TableType>,
+    functions: Vec<Index>,
+    tables: Vec<TableType>,
     memories: Vec<MemoryType>,
-    pub globals: Vec<Global<'a>>,
+    globals: Vec<Global<'a>>,
     exports: Vec<Export<'a>>,
-    pub elements: Vec<Element<'a>>,
+    elements: Vec<Element<'a>>,
     datas: Vec<Data<'a>>,
-    pub codes: Vec<FunctionBody<'a>>,
+    codes: Vec<FunctionBody<'a>>,
     customs: Vec<CustomSection<'a>>,
 }
 
-impl<'a> Default for Module<'a> {
-    fn default() -> Self {
-        Self {
+impl<'a> Module<'a> {
+    /// Parses and validates a module, with the default configuration
+    pub fn from_binary(data: &'a [u8]) -> Result<Self> {
+        Self::from_binary_with_config(data, &Config::default())
+    }
+
+    /// Parses and validates a module, rejecting any use
+    /// of the proposals which are not enabled
+    pub fn from_binary_with_config(data: &'a [u8], config: &Config) -> Result<Self> {
+        config.check()?;
+        let mut validator = Validator::new();
+        validator.wasm_features(config.features());
+
+        let mut module = Module {
             start_fn_idx: None,
+            config: *config,
+            names: Rc::default(),
             types: Vec::new(),
             imports: Vec::new(),
             functions: Vec::new(),
@@ -48,32 +64,128 @@ impl<'a> Default for Module<'a> {
             datas: Vec::new(),
             codes: Vec::new(),
             customs: Vec::new(),
+        };
+        let mut funcs = vec![];
+        for payload in Parser::new(0).parse_all(data) {
+            let payload = payload?;
+            if let ValidPayload::Func(func, body) = validator.payload(&payload).map_err(|err| {
+                anyhow!(
+                    "Invalid module: {} at offset {}",
+                    err.message(),
+                    err.offset()
+                )
+            })? {
+                let index = module.imported_funcs() + module.codes.len();
+                funcs.push((index as Index, func, body));
+            }
+            module = Self::map_payload(module, payload)?;
+        }
+
+        // NB
+        // Bodies are validated once the whole module is parsed,
+        // so that errors can refer to functions by their names
+        for (index, mut func, body) in funcs {
+            func.validate(&body).map_err(|err| {
+                anyhow!(
+                    "Invalid function {}: {} at offset {}",
+                    module.names.func_or_index(index),
+                    err.message(),
+                    err.offset()
+                )
+            })?;
         }
+
+        Ok(module)
     }
-}
 
-impl<'a> Module<'a> {
-    fn parse(data: &'a [u8]) -> Result<Self> {
-        let parser = Parser::new(0);
-        let mut parsed = parser.parse_all(data);
-        let acc = Module::default();
+    /// The index of the function to run on instantiation
+    pub fn start_fn_idx(&self) -> Option<Index> {
+        self.start_fn_idx
+    }
 
-        parsed.try_fold(acc, Self::map_payload)
+    pub fn config(&self) -> &Config {
+        &self.config
     }
 
-    pub fn from_binary(data: &'a [u8]) -> Result<Self> {
-        Self::parse(data)
+    /// The names decoded from the name section, if any
+    pub fn names(&self) -> &Names {
+        &self.names
+    }
+
+    pub(crate) fn shared_names(&self) -> Rc<Names> {
+        Rc::clone(&self.names)
+    }
+
+    /// The number of imported functions, which come first
+    /// in the function index space
+    pub fn imported_funcs(&self) -> usize {
+        self.imports
+            .iter()
+            .filter(|import| matches!(import.ty, ImportSectionEntryType::Function(_)))
+            .count()
+    }
+
+    pub fn imports(&self) -> &[Import<'a>] {
+        &self.imports
+    }
+
+    pub fn exports(&self) -> &[Export<'a>] {
+        &self.exports
+    }
+
+    /// The type indices of the functions defined by the module,
+    /// which come after the imported ones
+    pub fn functions(&self) -> &[Index] {
+        &self.functions
+    }
+
+    /// The bodies of the functions defined by the module
+    pub fn codes(&self) -> &[FunctionBody<'a>] {
+        &self.codes
+    }
+
+    /// The tables defined by the module, after the imported ones
+    pub fn tables(&self) -> &[TableType] {
+        &self.tables
+    }
+
+    /// The memories defined by the module, after the imported ones
+    pub fn memories(&self) -> &[MemoryType] {
+        &self.memories
+    }
+
+    /// The globals defined by the module, after the imported ones
+    pub fn globals(&self) -> &[Global<'a>] {
+        &self.globals
+    }
+
+    pub fn elements(&self) -> &[Element<'a>] {
+        &self.elements
+    }
+
+    pub fn datas(&self) -> &[Data<'a>] {
+        &self.datas
     }
 
     pub fn func_types(&self) -> Vec<FuncType> {
         self.types.clone()
     }
 
-    fn map_payload(
-        mut module: Module<'a>,
-        payload: Result<Payload<'a>, BinaryReaderError>,
-    ) -> Result<Module<'a>> {
-        match payload? {
+    /// The types of the globals in the global index space,
+    /// imported globals first
+    pub fn global_types(&self) -> Vec<GlobalType> {
+        self.imports
+            .iter()
+            .filter_map(|import| match import.ty {
+                ImportSectionEntryType::Global(ty) => Some(ty),
+                _ => None,
+            })
+            .chain(self.globals.iter().map(|global| global.ty))
+            .collect()
+    }
+
+    fn map_payload(mut module: Module<'a>, payload: Payload<'a>) -> Result<Module<'a>> {
+        match payload {
             Payload::TypeSection(reader) => {
                 let count = reader.get_count() as usize;
                 let func_types = Self::parse_type_section(reader)?;
@@ -163,6 +275,15 @@ impl<'a> Module<'a> {
                 data_offset,
                 range,
             } => {
+                // NB
+                // Malformed name sections are ignored, as
+                // allowed by the spec for custom sections
+                if name == "name" {
+                    if let Ok(names) = Names::parse(data, data_offset) {
+                        module.names = Rc::new(names);
+                    }
+                }
+
                 let custom_section = CustomSection {
                     name,
                     data_offset,
@@ -174,7 +295,6 @@ impl<'a> Module<'a> {
             }
 
             // TODO
-            // - Potentially record function names from the name custom section
             // - Track the starting point of the code section
             _ => (),
         }
@@ -265,3 +385,120 @@ impl<'a> Module<'a> {
         })
     }
 }
+
+impl<'a> fmt::Debug for Module<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let funcs: Vec<_> = (0..self.imported_funcs() + self.functions.len())
+            .map(|index| self.names.func_or_index(index as Index))
+            .collect();
+
+        f.debug_struct("Module")
+            .field("name", &self.names.module())
+            .field("funcs", &funcs)
+            .field("imports", &self.imports)
+            .field("exports", &self.exports)
+            .field("start", &self.start_fn_idx)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Module;
+    use crate::{config::Config, store::Store};
+
+    #[test]
+    fn immutable_global_set() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (global (mut i32) (i32.const 0))
+                 (global i32 (i32.const 0))
+                 (func
+                   i32.const 1
+                   global.set 0
+                   i32.const 1
+                   global.set 1))"#,
+        )
+        .unwrap();
+
+        let err = Module::from_binary(&bytes).err().unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid function func[0]: global is immutable"));
+    }
+
+    #[test]
+    fn constant_expr_globals() {
+        let valid = wat::parse_str(
+            r#"(module
+                 (import "env" "g" (global i32))
+                 (global i32 (global.get 0)))"#,
+        )
+        .unwrap();
+        assert!(Module::from_binary(&valid).is_ok());
+
+        let mutable_import = wat::parse_str(
+            r#"(module
+                 (import "env" "g" (global (mut i32)))
+                 (global i32 (global.get 0)))"#,
+        )
+        .unwrap();
+        assert!(Module::from_binary(&mutable_import).is_err());
+
+        let defined = wat::parse_str(
+            r#"(module
+                 (global i32 (i32.const 0))
+                 (global i32 (global.get 0)))"#,
+        )
+        .unwrap();
+        assert!(Module::from_binary(&defined).is_err());
+    }
+
+    #[test]
+    fn invalid_bodies() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (func (result i32) i32.const 0)
+                 (func (result i32) i64.const 0))"#,
+        )
+        .unwrap();
+        let err = Module::from_binary(&bytes).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid function func[1]: type mismatch"));
+
+        let bytes = wat::parse_str(r#"(module (func call 3))"#).unwrap();
+        let err = Module::from_binary(&bytes).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid function func[0]:"));
+
+        let bytes = wat::parse_str(r#"(module (start 1) (func))"#).unwrap();
+        let err = Module::from_binary(&bytes).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid module:"));
+    }
+
+    #[test]
+    fn features() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (func (param i32 i32) (result i32 i32)
+                   local.get 1
+                   local.get 0))"#,
+        )
+        .unwrap();
+        assert!(Module::from_binary(&bytes).is_ok());
+
+        let mut config = Config::new();
+        config.wasm_multi_value(false);
+        assert!(Module::from_binary_with_config(&bytes, &config).is_err());
+
+        let multi_memory = wat::parse_str(r#"(module (memory 1) (memory 1))"#).unwrap();
+        assert!(Module::from_binary(&multi_memory).is_err());
+        let mut config = Config::new();
+        config.wasm_multi_memory(true);
+        let module = Module::from_binary_with_config(&multi_memory, &config).unwrap();
+        assert_eq!(module.config(), &config);
+        assert!(Store::with_config(config)
+            .instantiate(&module, None)
+            .is_ok());
+    }
+}
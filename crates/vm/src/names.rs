@@ -0,0 +1,204 @@
+use crate::module::Index;
+use anyhow::Result;
+use std::collections::HashMap;
+use wasmparser::{IndirectNameMap, Name, NameMap, NameSectionReader, NamingReader};
+
+/// The names recorded in the `name` custom section of a module,
+/// including the subsections of the extended name section proposal.
+/// Names are only meant for diagnostics and are all optional.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Names {
+    module: Option<String>,
+    funcs: HashMap<Index, String>,
+    // Locals and labels are indexed by function first
+    locals: HashMap<Index, HashMap<Index, String>>,
+    labels: HashMap<Index, HashMap<Index, String>>,
+    types: HashMap<Index, String>,
+    tables: HashMap<Index, String>,
+    memories: HashMap<Index, String>,
+    globals: HashMap<Index, String>,
+    elems: HashMap<Index, String>,
+    datas: HashMap<Index, String>,
+}
+
+impl Names {
+    /// Decodes the payload of a name section found at the given
+    /// offset of the module binary
+    pub fn parse(data: &[u8], offset: usize) -> Result<Self> {
+        let mut reader = NameSectionReader::new(data, offset)?;
+        let mut names = Self::default();
+
+        while !reader.eof() {
+            match reader.read()? {
+                Name::Module(name) => names.module = Some(name.get_name()?.to_string()),
+                Name::Function(map) => names.funcs = name_map(map)?,
+                Name::Local(map) => names.locals = indirect_name_map(map)?,
+                Name::Label(map) => names.labels = indirect_name_map(map)?,
+                Name::Type(map) => names.types = name_map(map)?,
+                Name::Table(map) => names.tables = name_map(map)?,
+                Name::Memory(map) => names.memories = name_map(map)?,
+                Name::Global(map) => names.globals = name_map(map)?,
+                Name::Element(map) => names.elems = name_map(map)?,
+                Name::Data(map) => names.datas = name_map(map)?,
+                // Subsections of future proposals are skipped
+                Name::Unknown { .. } => (),
+            }
+        }
+
+        Ok(names)
+    }
+
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    pub fn func(&self, index: Index) -> Option<&str> {
+        self.funcs.get(&index).map(String::as_str)
+    }
+
+    /// The name of the function, falling back to its index
+    pub fn func_or_index(&self, index: Index) -> String {
+        match self.func(index) {
+            Some(name) => name.to_string(),
+            None => format!("func[{}]", index),
+        }
+    }
+
+    /// The name of a local, including parameters, of the given function
+    pub fn local(&self, func: Index, index: Index) -> Option<&str> {
+        self.locals
+            .get(&func)
+            .and_then(|locals| locals.get(&index))
+            .map(String::as_str)
+    }
+
+    /// The name of a label of the given function; labels are numbered
+    /// by the order of appearance of their structured instruction
+    pub fn label(&self, func: Index, index: Index) -> Option<&str> {
+        self.labels
+            .get(&func)
+            .and_then(|labels| labels.get(&index))
+            .map(String::as_str)
+    }
+
+    pub fn ty(&self, index: Index) -> Option<&str> {
+        self.types.get(&index).map(String::as_str)
+    }
+
+    pub fn table(&self, index: Index) -> Option<&str> {
+        self.tables.get(&index).map(String::as_str)
+    }
+
+    pub fn memory(&self, index: Index) -> Option<&str> {
+        self.memories.get(&index).map(String::as_str)
+    }
+
+    pub fn global(&self, index: Index) -> Option<&str> {
+        self.globals.get(&index).map(String::as_str)
+    }
+
+    pub fn elem(&self, index: Index) -> Option<&str> {
+        self.elems.get(&index).map(String::as_str)
+    }
+
+    pub fn data(&self, index: Index) -> Option<&str> {
+        self.datas.get(&index).map(String::as_str)
+    }
+}
+
+fn name_map(map: NameMap) -> Result<HashMap<Index, String>> {
+    naming_reader(map.get_map()?)
+}
+
+fn indirect_name_map(map: IndirectNameMap) -> Result<HashMap<Index, HashMap<Index, String>>> {
+    let mut reader = map.get_indirect_map()?;
+    let mut names = HashMap::with_capacity(reader.get_indirect_count() as usize);
+    for _ in 0..reader.get_indirect_count() {
+        let naming = reader.read()?;
+        names.insert(naming.indirect_index, naming_reader(naming.get_map()?)?);
+    }
+
+    Ok(names)
+}
+
+fn naming_reader(mut reader: NamingReader) -> Result<HashMap<Index, String>> {
+    let mut names = HashMap::with_capacity(reader.get_count() as usize);
+    for _ in 0..reader.get_count() {
+        let naming = reader.read()?;
+        names.insert(naming.index, naming.name.to_string());
+    }
+
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::module::Module;
+
+    #[test]
+    fn names() {
+        let bytes = wat::parse_str(
+            r#"(module $demo
+                 (type $sig (func))
+                 (import "env" "log" (func $log (param $msg i32)))
+                 (table $tab 1 funcref)
+                 (memory $mem 1)
+                 (global $counter (mut i32) (i32.const 0))
+                 (func $run (param $x i32) (local $tmp i64)
+                   (block $outer nop))
+                 (func (type $sig))
+                 (elem $elems (i32.const 0) $run)
+                 (data $bytes (i32.const 0) "a"))"#,
+        )
+        .unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let names = module.names();
+
+        assert_eq!(names.module(), Some("demo"));
+        assert_eq!(names.func(0), Some("log"));
+        assert_eq!(names.func(1), Some("run"));
+        assert_eq!(names.func(2), None);
+        assert_eq!(names.func_or_index(1), "run");
+        assert_eq!(names.func_or_index(2), "func[2]");
+        assert_eq!(names.local(0, 0), Some("msg"));
+        assert_eq!(names.local(1, 0), Some("x"));
+        assert_eq!(names.local(1, 1), Some("tmp"));
+        assert_eq!(names.label(1, 0), Some("outer"));
+        assert_eq!(names.ty(0), Some("sig"));
+        assert_eq!(names.table(0), Some("tab"));
+        assert_eq!(names.memory(0), Some("mem"));
+        assert_eq!(names.global(0), Some("counter"));
+        assert_eq!(names.elem(0), Some("elems"));
+        assert_eq!(names.data(0), Some("bytes"));
+
+        let debug = format!("{:?}", module);
+        assert!(debug.contains(r#"name: Some("demo")"#));
+        assert!(debug.contains(r#"funcs: ["log", "run", "func[2]"]"#));
+    }
+
+    #[test]
+    fn missing_names() {
+        let bytes = wat::parse_str(r#"(module (func))"#).unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+
+        assert_eq!(module.names().module(), None);
+        assert_eq!(module.names().func(0), None);
+    }
+
+    #[test]
+    fn names_in_errors() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (global i32 (i32.const 0))
+                 (func $my_crate::parse
+                   i32.const 1
+                   global.set 0))"#,
+        )
+        .unwrap();
+
+        let err = Module::from_binary(&bytes).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Invalid function my_crate::parse: global is immutable"));
+    }
+}
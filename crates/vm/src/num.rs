@@ -0,0 +1,265 @@
+use crate::trap::TrapCode;
+
+/// Integer semantics that can't be expressed directly
+/// through Rust's wrapping arithmetic
+pub trait Integer: Sized {
+    fn div_s(self, rhs: Self) -> Result<Self, TrapCode>;
+    fn div_u(self, rhs: Self) -> Result<Self, TrapCode>;
+    fn rem_s(self, rhs: Self) -> Result<Self, TrapCode>;
+    fn rem_u(self, rhs: Self) -> Result<Self, TrapCode>;
+}
+
+macro_rules! integer {
+    ($($ty:ty => $unsigned:ty),*) => {
+        $(
+            impl Integer for $ty {
+                fn div_s(self, rhs: Self) -> Result<Self, TrapCode> {
+                    if rhs == 0 {
+                        return Err(TrapCode::IntegerDivideByZero);
+                    }
+                    self.checked_div(rhs).ok_or(TrapCode::IntegerOverflow)
+                }
+
+                fn div_u(self, rhs: Self) -> Result<Self, TrapCode> {
+                    if rhs == 0 {
+                        return Err(TrapCode::IntegerDivideByZero);
+                    }
+                    Ok(((self as $unsigned) / (rhs as $unsigned)) as $ty)
+                }
+
+                fn rem_s(self, rhs: Self) -> Result<Self, TrapCode> {
+                    if rhs == 0 {
+                        return Err(TrapCode::IntegerDivideByZero);
+                    }
+                    // NB
+                    // The remainder of `MIN / -1` is 0, even if
+                    // the division itself overflows
+                    Ok(self.wrapping_rem(rhs))
+                }
+
+                fn rem_u(self, rhs: Self) -> Result<Self, TrapCode> {
+                    if rhs == 0 {
+                        return Err(TrapCode::IntegerDivideByZero);
+                    }
+                    Ok(((self as $unsigned) % (rhs as $unsigned)) as $ty)
+                }
+            }
+        )*
+    };
+}
+
+integer!(i32 => u32, i64 => u64);
+
+/// Floating point semantics that differ from Rust's
+///
+/// NB
+/// Whenever an arithmetic operation produces a NaN, the spec allows
+/// any arithmetic NaN as result; NaNs are canonicalized so that
+/// results are deterministic across platforms. `abs`, `neg` and
+/// `copysign` are pure bitwise operations and preserve payloads.
+pub trait Float: Sized {
+    /// Replaces any NaN with the positive canonical NaN
+    fn canonicalize(self) -> Self;
+    fn wasm_min(self, rhs: Self) -> Self;
+    fn wasm_max(self, rhs: Self) -> Self;
+    /// Rounds to the nearest integer, with ties to even
+    fn nearest(self) -> Self;
+}
+
+macro_rules! float {
+    ($($ty:ident => $canonical_nan:expr),*) => {
+        $(
+            impl Float for $ty {
+                fn canonicalize(self) -> Self {
+                    if self.is_nan() {
+                        $ty::from_bits($canonical_nan)
+                    } else {
+                        self
+                    }
+                }
+
+                fn wasm_min(self, rhs: Self) -> Self {
+                    if self.is_nan() || rhs.is_nan() {
+                        return $ty::NAN.canonicalize();
+                    }
+                    // -0 is smaller than +0
+                    if self == rhs {
+                        return if self.is_sign_negative() { self } else { rhs };
+                    }
+                    if self < rhs { self } else { rhs }
+                }
+
+                fn wasm_max(self, rhs: Self) -> Self {
+                    if self.is_nan() || rhs.is_nan() {
+                        return $ty::NAN.canonicalize();
+                    }
+                    // +0 is greater than -0
+                    if self == rhs {
+                        return if self.is_sign_positive() { self } else { rhs };
+                    }
+                    if self > rhs { self } else { rhs }
+                }
+
+                fn nearest(self) -> Self {
+                    if !self.is_finite() || self == 0.0 {
+                        return self.canonicalize();
+                    }
+                    let rounded = self.round();
+                    // `round` breaks ties away from zero
+                    let rounded = if (rounded - self).abs() == 0.5 {
+                        (self / 2.0).round() * 2.0
+                    } else {
+                        rounded
+                    };
+                    // Keep the sign of the operand when rounding to zero
+                    rounded.copysign(self)
+                }
+            }
+        )*
+    };
+}
+
+float!(f32 => 0x7fc0_0000, f64 => 0x7ff8_0000_0000_0000);
+
+macro_rules! trunc {
+    ($($name:ident: $float:ty => $int:ty as $ty:ty, $range:expr),*) => {
+        $(
+            /// Truncates a float into an integer, trapping on NaN and
+            /// on values which can't be represented by the integer type
+            pub fn $name(val: $float) -> Result<$ty, TrapCode> {
+                if val.is_nan() {
+                    return Err(TrapCode::InvalidConversionToInteger);
+                }
+                let truncated = val.trunc();
+                if !($range).contains(&truncated) {
+                    return Err(TrapCode::IntegerOverflow);
+                }
+                Ok(truncated as $int as $ty)
+            }
+        )*
+    };
+}
+
+// NB
+// The bounds are the exact powers of two delimiting the range of each
+// integer type, upper bound excluded; -0.0 truncates to an unsigned 0
+trunc! {
+    i32_trunc_f32_s: f32 => i32 as i32, -2147483648.0..2147483648.0,
+    i32_trunc_f32_u: f32 => u32 as i32, 0.0..4294967296.0,
+    i32_trunc_f64_s: f64 => i32 as i32, -2147483648.0..2147483648.0,
+    i32_trunc_f64_u: f64 => u32 as i32, 0.0..4294967296.0,
+    i64_trunc_f32_s: f32 => i64 as i64, -9223372036854775808.0..9223372036854775808.0,
+    i64_trunc_f32_u: f32 => u64 as i64, 0.0..18446744073709551616.0,
+    i64_trunc_f64_s: f64 => i64 as i64, -9223372036854775808.0..9223372036854775808.0,
+    i64_trunc_f64_u: f64 => u64 as i64, 0.0..18446744073709551616.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trap::TrapCode;
+
+    #[test]
+    fn div() {
+        assert_eq!(7i32.div_s(-2), Ok(-3));
+        assert_eq!((-7i64).div_s(2), Ok(-3));
+        assert_eq!((-1i32).div_u(2), Ok(i32::MAX));
+        assert_eq!(1i32.div_s(0), Err(TrapCode::IntegerDivideByZero));
+        assert_eq!(1i64.div_u(0), Err(TrapCode::IntegerDivideByZero));
+        assert_eq!(i32::MIN.div_s(-1), Err(TrapCode::IntegerOverflow));
+        assert_eq!(i64::MIN.div_s(-1), Err(TrapCode::IntegerOverflow));
+        assert_eq!(i64::MIN.div_u(-1), Ok(0));
+    }
+
+    #[test]
+    fn rem() {
+        assert_eq!((-7i32).rem_s(2), Ok(-1));
+        assert_eq!(7i64.rem_s(-2), Ok(1));
+        assert_eq!((-7i32).rem_u(2), Ok(1));
+        assert_eq!(i32::MIN.rem_s(-1), Ok(0));
+        assert_eq!(i64::MIN.rem_s(-1), Ok(0));
+        assert_eq!(1i32.rem_s(0), Err(TrapCode::IntegerDivideByZero));
+        assert_eq!(1i64.rem_u(0), Err(TrapCode::IntegerDivideByZero));
+    }
+
+    #[test]
+    fn canonicalize() {
+        let nan = f32::from_bits(0xffc0_0123);
+        assert_eq!(nan.canonicalize().to_bits(), 0x7fc0_0000);
+        assert_eq!((-1.5f64).canonicalize(), -1.5);
+        assert_eq!(
+            f64::from_bits(0x7ff0_0000_0000_0001)
+                .canonicalize()
+                .to_bits(),
+            0x7ff8_0000_0000_0000
+        );
+    }
+
+    #[test]
+    fn min_max() {
+        assert_eq!(1.0f32.wasm_min(2.0), 1.0);
+        assert_eq!(1.0f64.wasm_max(2.0), 2.0);
+        assert!(0.0f32.wasm_min(-0.0).is_sign_negative());
+        assert!((-0.0f32).wasm_min(0.0).is_sign_negative());
+        assert!(0.0f64.wasm_max(-0.0).is_sign_positive());
+        assert!((-0.0f64).wasm_max(0.0).is_sign_positive());
+        assert_eq!(f32::NAN.wasm_min(1.0).to_bits(), 0x7fc0_0000);
+        assert_eq!(1.0f64.wasm_max(f64::NAN).to_bits(), 0x7ff8_0000_0000_0000);
+    }
+
+    #[test]
+    fn nearest() {
+        assert_eq!(0.5f32.nearest(), 0.0);
+        assert_eq!(1.5f32.nearest(), 2.0);
+        assert_eq!(2.5f64.nearest(), 2.0);
+        assert_eq!((-2.5f64).nearest(), -2.0);
+        assert_eq!((-3.5f32).nearest(), -4.0);
+        assert_eq!(2.4f64.nearest(), 2.0);
+        assert_eq!(2.6f32.nearest(), 3.0);
+        assert!((-0.5f64).nearest().is_sign_negative());
+        assert!((-0.2f32).nearest().is_sign_negative());
+        assert_eq!(f64::INFINITY.nearest(), f64::INFINITY);
+        assert_eq!(8388609.0f32.nearest(), 8388609.0);
+        assert_eq!(4503599627370497.0f64.nearest(), 4503599627370497.0);
+    }
+
+    #[test]
+    fn trunc() {
+        assert_eq!(i32_trunc_f32_s(-2147483648.0), Ok(i32::MIN));
+        assert_eq!(
+            i32_trunc_f32_s(2147483648.0),
+            Err(TrapCode::IntegerOverflow)
+        );
+        assert_eq!(i32_trunc_f64_s(2147483647.9), Ok(i32::MAX));
+        assert_eq!(i32_trunc_f64_s(-2147483648.9), Ok(i32::MIN));
+        assert_eq!(
+            i32_trunc_f64_s(-2147483649.0),
+            Err(TrapCode::IntegerOverflow)
+        );
+        assert_eq!(i32_trunc_f32_u(-0.9), Ok(0));
+        assert_eq!(i32_trunc_f32_u(-1.0), Err(TrapCode::IntegerOverflow));
+        assert_eq!(i32_trunc_f64_u(4294967295.9), Ok(-1));
+        assert_eq!(
+            i32_trunc_f64_u(4294967296.0),
+            Err(TrapCode::IntegerOverflow)
+        );
+        assert_eq!(i64_trunc_f32_s(i64::MIN as f32), Ok(i64::MIN));
+        assert_eq!(
+            i64_trunc_f64_s(9223372036854775808.0),
+            Err(TrapCode::IntegerOverflow)
+        );
+        assert_eq!(i64_trunc_f64_u(18446744073709549568.0), Ok(-2048));
+        assert_eq!(
+            i64_trunc_f32_u(18446744073709551616.0),
+            Err(TrapCode::IntegerOverflow)
+        );
+        assert_eq!(
+            i32_trunc_f32_s(f32::NAN),
+            Err(TrapCode::InvalidConversionToInteger)
+        );
+        assert_eq!(
+            i64_trunc_f64_u(-f64::NAN),
+            Err(TrapCode::InvalidConversionToInteger)
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use anyhow::{Context, Result};
 
-#[derive(Default)]
 pub struct Stack<T> {
     stack: Vec<T>,
 }
 
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self { stack: Vec::new() }
+    }
+}
+
 impl<T> Stack<T> {
     pub fn push(&mut self, val: T) {
         self.stack.push(val);
@@ -19,6 +24,11 @@ impl<T> Stack<T> {
     pub fn is_empty(&self) -> bool {
         self.stack.is_empty()
     }
+
+    /// Iterates over the entries, starting from the top of the stack
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.stack.iter().rev()
+    }
 }
 
 #[cfg(test)]
@@ -57,4 +67,13 @@ mod tests {
         let mut stack = I32Stack::default();
         stack.pop().unwrap();
     }
+
+    #[test]
+    fn iter() {
+        let mut stack = I32Stack::default();
+        stack.push(1);
+        stack.push(2);
+
+        assert_eq!(stack.iter().copied().collect::<Vec<_>>(), vec![2, 1]);
+    }
 }
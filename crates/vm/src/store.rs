@@ -1,13 +1,20 @@
-use crate::addressable::{Addr, Addressable, Slottable};
+use crate::addressable::{Addr, Addressable, Slot};
+use crate::config::Config;
+use crate::fuel::{DefaultFuelCosts, FuelCosts};
 use crate::instance::{
-    elem::Elem, func::Func, global::Global, table::Table, Index as InstanceIndex, Instance,
+    data::Data, elem::Elem, func::Func, global::Global, memory::Memory, table::Table,
+    Index as InstanceIndex, Instance, WasmFunc,
 };
+use crate::instr::Kind;
+use crate::limiter::ResourceLimiter;
+use crate::linker::Extern;
 use crate::module::Module;
-use crate::val::{RefType, RefValue, ValueType};
-use crate::vm;
+use crate::trap::{Trap, TrapCode};
+use crate::val::{RefType, RefValue, Value};
+use crate::vm::{self, VM};
 use anyhow::{bail, Context, Result};
 use std::collections::HashMap;
-use wasmparser::{ElementItem, Global as GlobalReader};
+use wasmparser::{ElementItem, ExternalKind};
 
 #[derive(Default)]
 pub struct Store {
@@ -17,6 +24,16 @@ pub struct Store {
     funcs: Addressable<Func>,
     tables: Addressable<Table>,
     elems: Addressable<Elem>,
+    memories: Addressable<Memory>,
+    datas: Addressable<Data>,
+    limiter: Option<Box<dyn ResourceLimiter>>,
+    config: Config,
+    // The remaining fuel, when fuel consumption is enabled
+    fuel: Option<u64>,
+    fuel_costs: Option<Box<dyn FuelCosts>>,
+    // The number of wasm frames suspended while a host function
+    // runs, and of the invocations nested on the native stack
+    call_depth: (usize, usize),
 }
 
 impl<'a> Store {
@@ -24,127 +41,619 @@ impl<'a> Store {
         Self::default()
     }
 
+    /// Creates a store which only instantiates modules validated
+    /// against proposals enabled in the given configuration
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            config,
+            fuel: if config.fuel_enabled() { Some(0) } else { None },
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Adds fuel to the store, which must have been
+    /// created with fuel consumption enabled
+    pub fn add_fuel(&mut self, fuel: u64) -> Result<()> {
+        let remaining = self
+            .fuel
+            .as_mut()
+            .context("Fuel consumption is not enabled")?;
+        *remaining = remaining.saturating_add(fuel);
+
+        Ok(())
+    }
+
+    /// Consumes fuel from the store, returning the remaining fuel;
+    /// nothing is consumed if there isn't enough fuel left
+    pub fn consume_fuel(&mut self, fuel: u64) -> Result<u64> {
+        let remaining = self
+            .fuel
+            .as_mut()
+            .context("Fuel consumption is not enabled")?;
+        if fuel > *remaining {
+            bail!(
+                "Not enough fuel: {} requested, {} remaining",
+                fuel,
+                remaining
+            );
+        }
+        *remaining -= fuel;
+
+        Ok(*remaining)
+    }
+
+    /// The remaining fuel, if fuel consumption is enabled
+    pub fn fuel_remaining(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Replaces the default costs charged for each instruction
+    pub fn set_fuel_costs(&mut self, costs: impl FuelCosts + 'static) {
+        self.fuel_costs = Some(Box::new(costs));
+    }
+
+    /// Charges the fuel for executing an instruction; the fuel is left
+    /// untouched if there isn't enough of it to execute the instruction
+    pub(crate) fn charge_fuel(&mut self, kind: &Kind) -> Result<(), TrapCode> {
+        let remaining = match &mut self.fuel {
+            Some(remaining) => remaining,
+            None => return Ok(()),
+        };
+        let cost = match &self.fuel_costs {
+            Some(costs) => costs.cost(kind),
+            None => DefaultFuelCosts.cost(kind),
+        };
+        if cost > *remaining {
+            return Err(TrapCode::OutOfFuel);
+        }
+        *remaining -= cost;
+
+        Ok(())
+    }
+
+    pub(crate) fn call_depth(&self) -> (usize, usize) {
+        self.call_depth
+    }
+
+    pub(crate) fn set_call_depth(&mut self, frames: usize, invocations: usize) {
+        self.call_depth = (frames, invocations);
+    }
+
+    /// Configures the hooks consulted whenever a memory or a table grows
+    pub fn set_limiter(&mut self, limiter: impl ResourceLimiter + 'static) {
+        self.limiter = Some(Box::new(limiter));
+    }
+
+    /// Instantiates a module without imports; modules
+    /// with imports are instantiated through a `Linker`
     pub fn instantiate(&mut self, module: &'a Module, name: Option<String>) -> Result<Instance> {
+        self.instantiate_with_imports(module, name, Vec::new())
+    }
+
+    /// Instantiates a module with the given externs,
+    /// in the order of the imports of the module
+    pub(crate) fn instantiate_with_imports(
+        &mut self,
+        module: &'a Module,
+        name: Option<String>,
+        imports: Vec<Extern>,
+    ) -> Result<Instance> {
         let index = self.instances.len();
 
+        if !self.config.includes(module.config()) {
+            bail!(
+                "Module was validated with proposals which are not enabled in the store: {:?}",
+                module.config()
+            );
+        }
         if let Some(name) = &name {
             if self.instances_env.contains_key(name) {
                 bail!("Instance with name {} already exists", name);
             }
-            self.instances_env.insert(name.to_owned(), index);
         }
 
-        self.allocate(module, index)?;
-
-        let instance = Instance::new(module.func_types(), index);
+        // NB
+        // Instances are kept even if instantiation fails, since the
+        // entities allocated so far are owned by their index and may
+        // already be referenced from imported tables
+        let mut instance = Instance::new(module.func_types(), module.shared_names(), index);
+        let allocated = self.allocate(module, &mut instance, imports);
         self.instances.push(instance.clone());
+        allocated?;
+
+        // NB
+        // The start function runs once every other entity is initialized;
+        // the instance isn't registered if it traps, but the effects on
+        // imported entities remain visible
+        if let Some(start) = module.start_fn_idx() {
+            instance
+                .func_addr(start)
+                .and_then(|addr| VM::new().invoke(self, addr, &[]))
+                .context("Start function failed")?;
+        }
+
+        if let Some(name) = name {
+            self.instances_env.insert(name, index);
+        }
 
         Ok(instance)
     }
 
-    fn allocate(&mut self, module: &'a Module, index: InstanceIndex) -> Result<()> {
-        self.allocate_globals(&module.globals, index)?;
-        self.allocate_funcs(module, index)?;
-        self.allocate_tables(module, index)?;
-        self.allocate_elems(module, index)
+    /// The instance registered under the given name
+    pub fn instance_by_name(&self, name: &str) -> Option<&Instance> {
+        self.instances_env
+            .get(name)
+            .and_then(|index| self.instances.get(*index))
+    }
+
+    pub(crate) fn instance(&self, index: InstanceIndex) -> Result<&Instance> {
+        self.instances
+            .get(index)
+            .with_context(|| format!("Invalid instance index {}", index))
+    }
+
+    pub(crate) fn global(&self, addr: &Addr) -> Result<&Global> {
+        self.globals
+            .get(addr)
+            .with_context(|| format!("Invalid global address {:?}", addr))
+    }
+
+    pub(crate) fn global_mut(&mut self, addr: &Addr) -> Result<&mut Global> {
+        self.globals
+            .get_mut(addr)
+            .with_context(|| format!("Invalid global address {:?}", addr))
+    }
+
+    pub(crate) fn func(&self, addr: &Addr) -> Result<&Func> {
+        self.funcs
+            .get(addr)
+            .with_context(|| format!("Invalid function address {:?}", addr))
+    }
+
+    pub(crate) fn table(&self, addr: &Addr) -> Result<&Table> {
+        self.tables
+            .get(addr)
+            .with_context(|| format!("Invalid table address {:?}", addr))
+    }
+
+    pub(crate) fn table_mut(&mut self, addr: &Addr) -> Result<&mut Table> {
+        self.tables
+            .get_mut(addr)
+            .with_context(|| format!("Invalid table address {:?}", addr))
+    }
+
+    pub(crate) fn elem_mut(&mut self, addr: &Addr) -> Result<&mut Elem> {
+        self.elems
+            .get_mut(addr)
+            .with_context(|| format!("Invalid element segment address {:?}", addr))
+    }
+
+    /// Copies `len` elements between two tables, which might be the same one
+    pub(crate) fn copy_table(
+        &mut self,
+        dst_addr: &Addr,
+        src_addr: &Addr,
+        dst: u32,
+        src: u32,
+        len: u32,
+    ) -> Result<()> {
+        if dst_addr == src_addr {
+            return Ok(self.table_mut(dst_addr)?.copy(dst, src, len)?);
+        }
+
+        let elems = self.table(src_addr)?.elements(src, len)?.to_vec();
+        Ok(self.table_mut(dst_addr)?.write(dst, &elems)?)
+    }
+
+    /// Copies `len` elements of an element segment into a table
+    pub(crate) fn init_table(
+        &mut self,
+        table_addr: &Addr,
+        elem_addr: &Addr,
+        dst: u32,
+        src: u32,
+        len: u32,
+    ) -> Result<()> {
+        let elem = self
+            .elems
+            .get(elem_addr)
+            .with_context(|| format!("Invalid element segment address {:?}", elem_addr))?;
+        let elems = src
+            .checked_add(len)
+            .and_then(|end| elem.data().get(src as usize..end as usize))
+            .ok_or(TrapCode::TableOutOfBounds)?;
+        let table = self
+            .tables
+            .get_mut(table_addr)
+            .with_context(|| format!("Invalid table address {:?}", table_addr))?;
+
+        Ok(table.write(dst, elems)?)
+    }
+
+    /// Grows the table at the given address by the given number of
+    /// elements, returning its previous size on success
+    pub(crate) fn grow_table(
+        &mut self,
+        addr: &Addr,
+        delta: u32,
+        init: RefValue,
+    ) -> Result<Option<u32>> {
+        let table = self
+            .tables
+            .get_mut(addr)
+            .with_context(|| format!("Invalid table address {:?}", addr))?;
+
+        // The limiter is only consulted for requests within the maximum
+        let current = table.size();
+        let desired = match current.checked_add(delta) {
+            Some(desired) if desired <= table.limit() => desired,
+            _ => return Ok(None),
+        };
+        if let Some(limiter) = &mut self.limiter {
+            if !limiter.table_growing(*addr, current, desired, table.maximum()) {
+                return Ok(None);
+            }
+        }
+
+        Ok(table.grow(delta, init))
+    }
+
+    pub(crate) fn memory(&self, addr: &Addr) -> Result<&Memory> {
+        self.memories
+            .get(addr)
+            .with_context(|| format!("Invalid memory address {:?}", addr))
+    }
+
+    pub(crate) fn memory_mut(&mut self, addr: &Addr) -> Result<&mut Memory> {
+        self.memories
+            .get_mut(addr)
+            .with_context(|| format!("Invalid memory address {:?}", addr))
+    }
+
+    pub(crate) fn data_mut(&mut self, addr: &Addr) -> Result<&mut Data> {
+        self.datas
+            .get_mut(addr)
+            .with_context(|| format!("Invalid data segment address {:?}", addr))
     }
 
-    fn allocate_globals(
+    /// Copies `len` bytes between two memories, which might be the same one
+    pub(crate) fn copy_memory(
         &mut self,
-        globals: &Vec<GlobalReader>,
-        index: InstanceIndex,
+        dst_addr: &Addr,
+        src_addr: &Addr,
+        dst: u64,
+        src: u64,
+        len: u64,
     ) -> Result<()> {
-        globals.iter().enumerate().try_for_each(|(i, global)| {
-            let value = vm::resolve_constant_expr(&global.init_expr)?;
-            let elem_index = i
-                .try_into()
-                .with_context(|| format!("Conversion of {} to u32 failed", i))?;
-            self.globals
-                .push(index, elem_index, Global::new(value, global.ty));
+        if dst_addr == src_addr {
+            return Ok(self.memory_mut(dst_addr)?.copy(dst, src, len)?);
+        }
+
+        let bytes = self.memory(src_addr)?.bytes(src, len)?.to_vec();
+        Ok(self.memory_mut(dst_addr)?.write(dst, &bytes)?)
+    }
+
+    /// Copies `len` bytes of a data segment into a memory
+    pub(crate) fn init_memory(
+        &mut self,
+        memory_addr: &Addr,
+        data_addr: &Addr,
+        dst: u64,
+        src: u64,
+        len: u64,
+    ) -> Result<()> {
+        let data = self
+            .datas
+            .get(data_addr)
+            .with_context(|| format!("Invalid data segment address {:?}", data_addr))?;
+        let bytes = src
+            .checked_add(len)
+            .and_then(|end| data.bytes().get(src as usize..end as usize))
+            .ok_or(TrapCode::MemoryOutOfBounds)?;
+        let memory = self
+            .memories
+            .get_mut(memory_addr)
+            .with_context(|| format!("Invalid memory address {:?}", memory_addr))?;
+
+        Ok(memory.write(dst, bytes)?)
+    }
+
+    /// Grows the memory at the given address by the given number
+    /// of pages, returning its previous size on success
+    pub(crate) fn grow_memory(&mut self, addr: &Addr, delta: u64) -> Result<Option<u64>> {
+        let memory = self
+            .memories
+            .get_mut(addr)
+            .with_context(|| format!("Invalid memory address {:?}", addr))?;
+
+        // The limiter is only consulted for requests within the maximum
+        let current = memory.size();
+        let desired = match current.checked_add(delta) {
+            Some(desired) if desired <= memory.limit() => desired,
+            _ => return Ok(None),
+        };
+        if let Some(limiter) = &mut self.limiter {
+            if !limiter.memory_growing(*addr, current, desired, memory.maximum()) {
+                return Ok(None);
+            }
+        }
+
+        Ok(memory.grow(delta))
+    }
+
+    fn allocate(
+        &mut self,
+        module: &'a Module,
+        instance: &mut Instance,
+        imports: Vec<Extern>,
+    ) -> Result<()> {
+        self.allocate_imports(module, instance, imports)?;
+        // NB
+        // Functions are allocated first, as global initializers
+        // can refer to them with `ref.func`
+        self.allocate_funcs(module, instance)?;
+        self.allocate_globals(module, instance)?;
+        self.allocate_tables(module, instance)?;
+        self.allocate_memories(module, instance)?;
+        let elems = self.allocate_elems(module, instance)?;
+        let datas = self.allocate_datas(module, instance)?;
+        self.init_segments(instance, elems, datas)?;
+        Self::resolve_exports(module, instance)?;
+
+        Ok(())
+    }
+
+    fn resolve_exports(module: &'a Module, instance: &mut Instance) -> Result<()> {
+        module.exports().iter().try_for_each(|export| {
+            let addr = match export.kind {
+                ExternalKind::Function => instance.func_addr(export.index)?,
+                ExternalKind::Table => instance.table_addr(export.index)?,
+                ExternalKind::Memory => instance.memory_addr(export.index)?,
+                ExternalKind::Global => instance.global_addr(export.index)?,
+                kind => bail!("Export {} of kind {:?} not supported", export.field, kind),
+            };
+            instance.push_export(export.field, addr);
+
             Ok(())
         })
     }
 
-    fn allocate_funcs(&mut self, module: &'a Module, index: InstanceIndex) -> Result<()> {
-        let functions = &module.functions;
-        let codes = &module.codes;
+    fn allocate_imports(
+        &mut self,
+        module: &'a Module,
+        instance: &mut Instance,
+        imports: Vec<Extern>,
+    ) -> Result<()> {
+        if imports.len() != module.imports().len() {
+            bail!(
+                "Module expects {} imports, got {}",
+                module.imports().len(),
+                imports.len()
+            );
+        }
+
+        imports.into_iter().try_for_each(|import| {
+            let addr = match import {
+                // Host functions are allocated as part of the importing instance
+                Extern::HostFunc(func) => {
+                    self.funcs
+                        .push(instance.index(), instance.len(Slot::Func), Func::Host(func))
+                }
+                Extern::Export(addr) => addr,
+            };
+
+            instance.push(addr)
+        })
+    }
+
+    /// The values of the imported globals of an instance, which are
+    /// the only globals constant expressions can refer to
+    fn imported_globals(&self, module: &'a Module, instance: &Instance) -> Result<Vec<Value>> {
+        let imported = module.global_types().len() - module.globals().len();
+        (0..imported as u32)
+            .map(|i| Ok(self.global(&instance.global_addr(i)?)?.value()))
+            .collect()
+    }
+
+    fn allocate_globals(&mut self, module: &'a Module, instance: &mut Instance) -> Result<()> {
+        let imported_globals = self.imported_globals(module, instance)?;
+
+        module.globals().iter().try_for_each(|global| {
+            let value = vm::resolve_constant_expr(&global.init_expr, &imported_globals, instance)?;
+            let addr = self.globals.push(
+                instance.index(),
+                instance.len(Slot::Global),
+                Global::new(value, global.ty),
+            );
+            instance.push(addr)
+        })
+    }
+
+    fn allocate_funcs(&mut self, module: &'a Module, instance: &mut Instance) -> Result<()> {
+        let functions = module.functions();
+        let codes = module.codes();
         let types = &module.func_types();
 
         codes
             .iter()
             .zip(functions)
-            .try_for_each(|(body, func_index)| {
+            .try_for_each(|(body, type_index)| {
                 let locals = body.get_locals_reader()?;
                 let ops = body.get_operators_reader()?;
                 let ty = types
-                    .get(*func_index as usize)
-                    .with_context(|| format!("Invalid function index {}", func_index))?;
+                    .get(*type_index as usize)
+                    .with_context(|| format!("Invalid type index {}", type_index))?;
 
-                self.funcs
-                    .push(index, *func_index, Func::new(ty.clone(), locals, ops)?);
-                Ok(())
+                let func = WasmFunc::new(ty.clone(), locals, ops)?;
+                let addr =
+                    self.funcs
+                        .push(instance.index(), instance.len(Slot::Func), Func::Wasm(func));
+                instance.push(addr)
             })
     }
 
-    fn allocate_tables(&mut self, module: &'a Module, index: InstanceIndex) -> Result<()> {
-        let tables = &module.tables;
-        tables.iter().enumerate().try_for_each(|(i, t)| {
+    fn allocate_tables(&mut self, module: &'a Module, instance: &mut Instance) -> Result<()> {
+        let tables = module.tables();
+        tables.iter().try_for_each(|t| {
             let ty = RefType::try_from(t.element_type)?;
-            let elem_index = i
-                .try_into()
-                .with_context(|| format!("Conversion of {} to u32 failed", i))?;
-            self.tables
-                .push(index, elem_index, Table::new(ty, t.initial, t.maximum)?);
-
-            Ok::<(), anyhow::Error>(())
-        })?;
+            let addr = Addr::new_unsafe(instance.index(), instance.len(Slot::Table), Slot::Table);
+            if let Some(limiter) = &mut self.limiter {
+                if !limiter.table_growing(addr, 0, t.initial, t.maximum) {
+                    bail!(
+                        "Table of {} elements denied by the resource limiter",
+                        t.initial
+                    );
+                }
+            }
+            let addr = self.tables.push(
+                instance.index(),
+                instance.len(Slot::Table),
+                Table::new(ty, t.initial, t.maximum)?,
+            );
+            instance.push(addr)
+        })
+    }
 
-        Ok(())
+    fn allocate_memories(&mut self, module: &'a Module, instance: &mut Instance) -> Result<()> {
+        let memories = module.memories();
+        memories.iter().try_for_each(|m| {
+            if m.memory64 {
+                bail!("64-bit memories are not supported");
+            }
+            // NB
+            // The initial pages are the largest allocation of most
+            // memories, the limiter is asked before they're allocated
+            let addr = Addr::new_unsafe(instance.index(), instance.len(Slot::Memory), Slot::Memory);
+            if let Some(limiter) = &mut self.limiter {
+                if !limiter.memory_growing(addr, 0, m.initial, m.maximum) {
+                    bail!(
+                        "Memory of {} pages denied by the resource limiter",
+                        m.initial
+                    );
+                }
+            }
+            let addr = self.memories.push(
+                instance.index(),
+                instance.len(Slot::Memory),
+                Memory::new(m.initial, m.maximum)?,
+            );
+            instance.push(addr)
+        })
     }
 
-    fn allocate_elems(&mut self, module: &'a Module, index: InstanceIndex) -> Result<()> {
-        let elements = &module.elements;
-        elements
+    fn allocate_elems(&self, module: &'a Module, instance: &Instance) -> Result<Vec<Elem>> {
+        let imported_globals = self.imported_globals(module, instance)?;
+        module
+            .elements()
             .iter()
-            .enumerate()
-            .try_for_each(|(element_index, e)| {
+            .map(|e| {
                 let ty = RefType::try_from(e.ty)?;
-                let items_reader = e.items.get_items_reader()?;
-                let acc: Vec<RefValue> = vec![];
-
-                let acc = items_reader.into_iter().try_fold(acc, |mut acc, item| {
-                    let rv = match item? {
-                        ElementItem::Func(idx) => {
-                            let func_addr = Addr::new_unsafe(index, idx, Func::slot());
-                            RefValue::FuncRef(func_addr)
+                let items = e
+                    .items
+                    .get_items_reader()?
+                    .into_iter()
+                    .map(|item| match item? {
+                        ElementItem::Func(idx) => Ok(RefValue::FuncRef(instance.func_addr(idx)?)),
+                        ElementItem::Expr(init) => {
+                            vm::resolve_ref_expr(&init, &imported_globals, instance)
                         }
-                        ElementItem::Expr(init) => vm::resolve_funcref_expr(&init, index)?,
-                    };
-                    acc.push(rv);
-
-                    Ok::<Vec<RefValue>, anyhow::Error>(acc)
-                })?;
-
-                let elem_instance = Elem::new(ty, acc, &e.kind)?;
-
-                if elem_instance.is_active() {
-                    let (table_index, offset) = elem_instance.metadata().with_context(|| {
-                        format!("No metadata found on element kind {:?}", elem_instance)
-                    })?;
-                    let table_addr = Addr::new_unsafe(index, table_index, Table::slot());
-                    let table = self
-                        .tables
-                        .get_mut(&table_addr)
-                        .with_context(|| format!("Invalid table address {:?}", table_addr))?;
-                    table.init(&offset, elem_instance.data.clone())?;
-                }
+                    })
+                    .collect::<Result<Vec<_>>>()?;
 
-                self.elems
-                    .push(index, u32::try_from(element_index)?, elem_instance);
+                Elem::new(ty, items, &e.kind, &imported_globals, instance)
+            })
+            .collect()
+    }
 
-                Ok::<(), anyhow::Error>(())
-            })?;
+    fn allocate_datas(&self, module: &'a Module, instance: &Instance) -> Result<Vec<Data>> {
+        let imported_globals = self.imported_globals(module, instance)?;
+        module
+            .datas()
+            .iter()
+            .map(|d| Data::new(d.data.to_vec(), &d.kind, &imported_globals, instance))
+            .collect()
+    }
+
+    /// Writes the active element and data segments into their tables
+    /// and memories, and stores the segments in the instance
+    fn init_segments(
+        &mut self,
+        instance: &Instance,
+        elems: Vec<Elem>,
+        datas: Vec<Data>,
+    ) -> Result<()> {
+        // NB
+        // Every active segment is bounds checked before any of them is
+        // written, so that a failed instantiation leaves tables and
+        // memories untouched, including the imported ones
+        for elem in &elems {
+            if let Some((table_index, offset)) = elem.metadata() {
+                self.table(&instance.table_addr(table_index)?)?
+                    .check_bounds(elem_offset(offset)?, u32::try_from(elem.data().len())?)
+                    .map_err(Trap::from)
+                    .context("Element segment does not fit in table")?;
+            }
+        }
+        for data in &datas {
+            if let Some((memory_index, offset)) = data.metadata() {
+                self.memory(&instance.memory_addr(memory_index)?)?
+                    .check_bounds(data_offset(offset)?, data.bytes().len() as u64)
+                    .map_err(Trap::from)
+                    .context("Data segment does not fit in memory")?;
+            }
+        }
+
+        for (elem_index, mut elem) in elems.into_iter().enumerate() {
+            if let Some((table_index, offset)) = elem.metadata() {
+                self.table_mut(&instance.table_addr(table_index)?)?
+                    .init(offset, elem.data())?;
+            }
+            // Active and declarative segments are dropped once instantiated
+            if !elem.is_passive() {
+                elem.clear();
+            }
+
+            self.elems
+                .push(instance.index(), u32::try_from(elem_index)?, elem);
+        }
+
+        for (data_index, mut data) in datas.into_iter().enumerate() {
+            if let Some((memory_index, offset)) = data.metadata() {
+                let offset = data_offset(offset)?;
+                self.memory_mut(&instance.memory_addr(memory_index)?)?
+                    .write(offset, data.bytes())?;
+                // Active segments are dropped once they are copied
+                data.clear();
+            }
+
+            self.datas
+                .push(instance.index(), u32::try_from(data_index)?, data);
+        }
 
         Ok(())
     }
 }
+
+fn elem_offset(offset: &Value) -> Result<u32> {
+    match offset {
+        Value::I32(v) => Ok(*v as u32),
+        v => bail!(
+            "Unexpected value in element segment offset: {:?}, expected ValueType::I32",
+            v
+        ),
+    }
+}
+
+fn data_offset(offset: &Value) -> Result<u64> {
+    match offset {
+        Value::I32(v) => Ok(*v as u32 as u64),
+        v => bail!(
+            "Unexpected value in data segment offset: {:?}, expected ValueType::I32",
+            v
+        ),
+    }
+}
@@ -0,0 +1,169 @@
+use crate::instance::Index as InstanceIndex;
+use std::fmt;
+
+/// The reason why the execution of an instruction cannot continue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapCode {
+    Unreachable,
+    IntegerOverflow,
+    IntegerDivideByZero,
+    InvalidConversionToInteger,
+    MemoryOutOfBounds,
+    TableOutOfBounds,
+    IndirectCallTypeMismatch,
+    NullReference,
+    StackExhaustion,
+    OutOfFuel,
+    HostError,
+}
+
+impl fmt::Display for TrapCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            TrapCode::Unreachable => "unreachable instruction executed",
+            TrapCode::IntegerOverflow => "integer overflow",
+            TrapCode::IntegerDivideByZero => "integer divide by zero",
+            TrapCode::InvalidConversionToInteger => "invalid conversion to integer",
+            TrapCode::MemoryOutOfBounds => "out of bounds memory access",
+            TrapCode::TableOutOfBounds => "out of bounds table access",
+            TrapCode::IndirectCallTypeMismatch => "indirect call type mismatch",
+            TrapCode::NullReference => "null reference",
+            TrapCode::StackExhaustion => "call stack exhausted",
+            TrapCode::OutOfFuel => "all fuel consumed",
+            TrapCode::HostError => "host function failed",
+        };
+
+        write!(f, "wasm trap: {}", msg)
+    }
+}
+
+impl std::error::Error for TrapCode {}
+
+/// A wasm function on the call stack when a trap was raised
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameInfo {
+    instance: InstanceIndex,
+    func_index: u32,
+    // The name of the function from the name section
+    func_name: Option<String>,
+    offset: usize,
+}
+
+impl FrameInfo {
+    pub(crate) fn new(
+        instance: InstanceIndex,
+        func_index: u32,
+        func_name: Option<String>,
+        offset: usize,
+    ) -> Self {
+        Self {
+            instance,
+            func_index,
+            func_name,
+            offset,
+        }
+    }
+
+    pub fn instance(&self) -> InstanceIndex {
+        self.instance
+    }
+
+    /// The index of the function in the index space of its instance
+    pub fn func_index(&self) -> u32 {
+        self.func_index
+    }
+
+    pub fn func_name(&self) -> Option<&str> {
+        self.func_name.as_deref()
+    }
+
+    /// The offset of the executing instruction in the module binary
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+/// A runtime trap, along with the wasm frames that were on the call
+/// stack when it was raised. Traps are surfaced through `anyhow::Error`
+/// and can be recovered by downcasting.
+#[derive(Debug)]
+pub struct Trap {
+    code: TrapCode,
+    backtrace: Vec<FrameInfo>,
+    // The error returned by a host function
+    source: Option<anyhow::Error>,
+}
+
+impl Trap {
+    pub fn new(code: TrapCode) -> Self {
+        Self {
+            code,
+            backtrace: vec![],
+            source: None,
+        }
+    }
+
+    /// Wraps the error of a host function; errors which are already
+    /// traps, e.g. raised by wasm code called back by the host, are
+    /// propagated as is
+    pub(crate) fn host(err: anyhow::Error) -> anyhow::Error {
+        if err.is::<Trap>() || err.is::<TrapCode>() {
+            return err;
+        }
+
+        Self {
+            code: TrapCode::HostError,
+            backtrace: vec![],
+            source: Some(err),
+        }
+        .into()
+    }
+
+    /// Converts an error carrying a trap code into a trap,
+    /// leaving any other error untouched
+    pub(crate) fn from_error(err: anyhow::Error) -> Result<Self, anyhow::Error> {
+        err.downcast::<Trap>()
+            .or_else(|err| err.downcast::<TrapCode>().map(Trap::new))
+    }
+
+    pub fn code(&self) -> TrapCode {
+        self.code
+    }
+
+    /// The wasm frames on the call stack, innermost first
+    pub fn backtrace(&self) -> &[FrameInfo] {
+        &self.backtrace
+    }
+
+    pub(crate) fn push_frame(&mut self, frame: FrameInfo) {
+        self.backtrace.push(frame);
+    }
+}
+
+impl From<TrapCode> for Trap {
+    fn from(code: TrapCode) -> Self {
+        Self::new(code)
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code)?;
+        for (index, frame) in self.backtrace.iter().enumerate() {
+            write!(f, "\n  {}: instance {} ", index, frame.instance)?;
+            match &frame.func_name {
+                Some(name) => write!(f, "{}", name)?,
+                None => write!(f, "func[{}]", frame.func_index)?,
+            }
+            write!(f, " @ {:#x}", frame.offset)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Trap {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_ref().map(|err| err.as_ref())
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{
+    handle::FuncHandle,
+    store::Store,
+    val::{RefType, RefValue, Value, ValueType},
+    vm::VM,
+};
+use anyhow::{bail, Result};
+use std::marker::PhantomData;
+
+/// A Rust type which maps to a wasm value type
+pub trait WasmTy: Sized {
+    fn ty() -> ValueType;
+    fn into_value(self) -> Value;
+    fn from_value(val: Value) -> Result<Self>;
+}
+
+macro_rules! wasm_ty {
+    ($($ty:ty => $value_type:ident),*) => {
+        $(
+            impl WasmTy for $ty {
+                fn ty() -> ValueType {
+                    ValueType::$value_type
+                }
+
+                fn into_value(self) -> Value {
+                    self.into()
+                }
+
+                fn from_value(val: Value) -> Result<Self> {
+                    val.try_into()
+                }
+            }
+        )*
+    };
+}
+
+wasm_ty!(i32 => I32, i64 => I64, f32 => F32, f64 => F64);
+
+/// Function references, where `None` is the null reference
+impl WasmTy for Option<FuncHandle> {
+    fn ty() -> ValueType {
+        ValueType::RefType(RefType::FuncRef)
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Some(func) => Value::Ref(RefValue::FuncRef(func.addr())),
+            None => Value::Ref(RefValue::Null(RefType::FuncRef)),
+        }
+    }
+
+    fn from_value(val: Value) -> Result<Self> {
+        match val {
+            Value::Ref(RefValue::FuncRef(addr)) => Ok(Some(FuncHandle::new(addr))),
+            Value::Ref(RefValue::Null(RefType::FuncRef)) => Ok(None),
+            v => bail!(
+                "Type mismatch: expected ValueType::RefType(FuncRef), got {:?}",
+                v
+            ),
+        }
+    }
+}
+
+/// A sequence of wasm values, used for the parameters and the results
+/// of functions; implemented for `()`, single values and tuples
+pub trait WasmTypes: Sized {
+    fn types() -> Vec<ValueType>;
+    fn into_values(self) -> Vec<Value>;
+    fn from_values(values: Vec<Value>) -> Result<Self>;
+}
+
+impl<T: WasmTy> WasmTypes for T {
+    fn types() -> Vec<ValueType> {
+        vec![T::ty()]
+    }
+
+    fn into_values(self) -> Vec<Value> {
+        vec![self.into_value()]
+    }
+
+    fn from_values(values: Vec<Value>) -> Result<Self> {
+        let (val,) = <(T,)>::from_values(values)?;
+        Ok(val)
+    }
+}
+
+impl WasmTypes for () {
+    fn types() -> Vec<ValueType> {
+        vec![]
+    }
+
+    fn into_values(self) -> Vec<Value> {
+        vec![]
+    }
+
+    fn from_values(values: Vec<Value>) -> Result<Self> {
+        check_len::<Self>(&values)
+    }
+}
+
+macro_rules! wasm_types {
+    ($($t:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($t: WasmTy),*> WasmTypes for ($($t,)*) {
+            fn types() -> Vec<ValueType> {
+                vec![$($t::ty()),*]
+            }
+
+            fn into_values(self) -> Vec<Value> {
+                let ($($t,)*) = self;
+                vec![$($t.into_value()),*]
+            }
+
+            fn from_values(values: Vec<Value>) -> Result<Self> {
+                check_len::<Self>(&values)?;
+                let mut values = values.into_iter();
+                $(
+                    // The length was checked above
+                    let $t = match values.next() {
+                        Some(val) => $t::from_value(val)?,
+                        None => bail!("Missing value"),
+                    };
+                )*
+
+                Ok(($($t,)*))
+            }
+        }
+    };
+}
+
+wasm_types!(A);
+wasm_types!(A, B);
+wasm_types!(A, B, C);
+wasm_types!(A, B, C, D);
+wasm_types!(A, B, C, D, E);
+wasm_types!(A, B, C, D, E, F);
+wasm_types!(A, B, C, D, E, F, G);
+wasm_types!(A, B, C, D, E, F, G, H);
+
+fn check_len<T: WasmTypes>(values: &[Value]) -> Result<()> {
+    let expected = T::types().len();
+    if values.len() != expected {
+        bail!("Expected {} values, got {}", expected, values.len());
+    }
+
+    Ok(())
+}
+
+/// A function whose type has been checked against `Params`
+/// and `Results`, which can be called with Rust values
+pub struct TypedFunc<Params, Results> {
+    func: FuncHandle,
+    ty: PhantomData<fn(Params) -> Results>,
+}
+
+impl<Params: WasmTypes, Results: WasmTypes> TypedFunc<Params, Results> {
+    /// Checks the type of the function once, so that
+    /// calls don't need to inspect values
+    pub fn new(store: &Store, func: FuncHandle) -> Result<Self> {
+        let ty = func.ty(store)?;
+        let params = ty
+            .params
+            .iter()
+            .map(|ty| ValueType::try_from(*ty))
+            .collect::<Result<Vec<_>>>()?;
+        let results = ty
+            .returns
+            .iter()
+            .map(|ty| ValueType::try_from(*ty))
+            .collect::<Result<Vec<_>>>()?;
+
+        if params != Params::types() || results != Results::types() {
+            bail!(
+                "Type mismatch: expected {:?} -> {:?}, got {:?} -> {:?}",
+                Params::types(),
+                Results::types(),
+                params,
+                results
+            );
+        }
+
+        Ok(Self {
+            func,
+            ty: PhantomData,
+        })
+    }
+
+    pub fn func(&self) -> FuncHandle {
+        self.func
+    }
+
+    pub fn call(&self, store: &mut Store, params: Params) -> Result<Results> {
+        let results = VM::new().invoke(store, self.func.addr(), &params.into_values())?;
+        Results::from_values(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypedFunc;
+    use crate::{handle::FuncHandle, linker::Linker, module::Module, store::Store};
+
+    #[test]
+    fn typed_calls() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (func (export "convert") (param i32 i64) (result f32)
+                   local.get 0
+                   i64.extend_i32_s
+                   local.get 1
+                   i64.add
+                   f32.convert_i64_s)
+                 (func (export "swap") (param f64 i32) (result i32 f64)
+                   local.get 1
+                   local.get 0)
+                 (func $nop (export "nop"))
+                 (func (export "ref") (param i32) (result funcref)
+                   (if (result funcref) (local.get 0)
+                     (then ref.func $nop)
+                     (else ref.null func))))"#,
+        )
+        .unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let mut store = Store::new();
+        let instance = store.instantiate(&module, None).unwrap();
+
+        let convert = instance
+            .get_typed_func::<(i32, i64), f32>(&store, "convert")
+            .unwrap();
+        assert_eq!(convert.call(&mut store, (1, 2)).unwrap(), 3.0);
+
+        let swap = instance
+            .get_typed_func::<(f64, i32), (i32, f64)>(&store, "swap")
+            .unwrap();
+        assert_eq!(swap.call(&mut store, (1.5, 2)).unwrap(), (2, 1.5));
+
+        let nop = instance.get_typed_func::<(), ()>(&store, "nop").unwrap();
+        nop.call(&mut store, ()).unwrap();
+
+        let func_ref = instance
+            .get_typed_func::<i32, Option<FuncHandle>>(&store, "ref")
+            .unwrap();
+        assert_eq!(func_ref.call(&mut store, 0).unwrap(), None);
+        assert_eq!(func_ref.call(&mut store, 1).unwrap(), Some(nop.func()));
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let bytes =
+            wat::parse_str(r#"(module (func (export "f") (param i32) (result i32) local.get 0))"#)
+                .unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let mut store = Store::new();
+        let instance = store.instantiate(&module, None).unwrap();
+        let func = instance.get_func(&store, "f").unwrap();
+
+        assert!(TypedFunc::<i32, i32>::new(&store, func).is_ok());
+        assert!(TypedFunc::<i64, i32>::new(&store, func).is_err());
+        assert!(TypedFunc::<i32, ()>::new(&store, func).is_err());
+        assert!(TypedFunc::<(i32, i32), i32>::new(&store, func).is_err());
+    }
+
+    #[test]
+    fn typed_host_funcs() {
+        let mut linker = Linker::new();
+        linker
+            .typed_func("env", "mul_add", |_, (a, b, c): (i32, i32, i64)| {
+                Ok((a as i64) * (b as i64) + c)
+            })
+            .unwrap();
+
+        let bytes = wat::parse_str(
+            r#"(module
+                 (import "env" "mul_add" (func $mul_add (param i32 i32 i64) (result i64)))
+                 (func (export "run") (result i64)
+                   i32.const 6
+                   i32.const 7
+                   i64.const 0
+                   call $mul_add))"#,
+        )
+        .unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let mut store = Store::new();
+        let instance = linker.instantiate(&mut store, &module, None).unwrap();
+
+        let run = instance.get_typed_func::<(), i64>(&store, "run").unwrap();
+        assert_eq!(run.call(&mut store, ()).unwrap(), 42);
+    }
+}
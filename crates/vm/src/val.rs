@@ -3,7 +3,7 @@ use crate::addressable::Addr;
 use anyhow::bail;
 use wasmparser::Type;
 
-#[derive(PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ValueType {
     I32,
     I64,
@@ -18,7 +18,7 @@ pub enum RefType {
     ExternRef,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Value {
     I32(i32),
     I64(i64),
@@ -50,8 +50,19 @@ impl RefValue {
 }
 
 impl Value {
+    /// The default value of a given type, used to initialize locals
+    pub fn default_for(ty: ValueType) -> Self {
+        match ty {
+            ValueType::I32 => Value::I32(0),
+            ValueType::I64 => Value::I64(0),
+            ValueType::F32 => Value::F32(0),
+            ValueType::F64 => Value::F64(0),
+            ValueType::RefType(t) => Value::Ref(RefValue::Null(t)),
+        }
+    }
+
     pub fn ty(&self) -> ValueType {
-        match &*self {
+        match self {
             Value::I32(_) => ValueType::I32,
             Value::I64(_) => ValueType::I64,
             Value::F32(_) => ValueType::F32,
@@ -59,7 +70,7 @@ impl Value {
             Value::Ref(v) => match v {
                 RefValue::FuncRef(_) => ValueType::RefType(RefType::FuncRef),
                 RefValue::ExternRef => ValueType::RefType(RefType::ExternRef),
-                RefValue::Null(t) => ValueType::RefType(t.clone()),
+                RefValue::Null(t) => ValueType::RefType(*t),
             },
         }
     }
@@ -83,6 +94,19 @@ impl TryFrom<Type> for ValueType {
     }
 }
 
+impl From<ValueType> for Type {
+    fn from(ty: ValueType) -> Type {
+        match ty {
+            ValueType::I32 => Type::I32,
+            ValueType::I64 => Type::I64,
+            ValueType::F32 => Type::F32,
+            ValueType::F64 => Type::F64,
+            ValueType::RefType(RefType::ExternRef) => Type::ExternRef,
+            ValueType::RefType(RefType::FuncRef) => Type::FuncRef,
+        }
+    }
+}
+
 impl TryFrom<Type> for RefType {
     type Error = anyhow::Error;
 
@@ -94,3 +118,56 @@ impl TryFrom<Type> for RefType {
         }
     }
 }
+
+/// Extracts a value whose type was checked by the validator; a
+/// mismatch is a bug of the interpreter, only caught in debug builds
+pub(crate) trait FromValue {
+    fn from_value(val: Value) -> Self;
+}
+
+macro_rules! value_conversions {
+    ($($ty:ty => $variant:ident, $from:expr, $into:expr, $fallback:expr);*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(val: $ty) -> Self {
+                    Value::$variant($into(val))
+                }
+            }
+
+            impl TryFrom<Value> for $ty {
+                type Error = anyhow::Error;
+
+                fn try_from(val: Value) -> anyhow::Result<$ty> {
+                    match val {
+                        Value::$variant(v) => Ok($from(v)),
+                        v => bail!(
+                            "Type mismatch: expected ValueType::{}, got {:?}",
+                            stringify!($variant),
+                            v
+                        ),
+                    }
+                }
+            }
+
+            impl FromValue for $ty {
+                fn from_value(val: Value) -> $ty {
+                    match val {
+                        Value::$variant(v) => $from(v),
+                        v => {
+                            debug_assert!(false, "Type mismatch: expected ValueType::{}, got {:?}", stringify!($variant), v);
+                            $fallback
+                        }
+                    }
+                }
+            }
+        )*
+    };
+}
+
+value_conversions! {
+    i32 => I32, |v| v, |v| v, 0;
+    i64 => I64, |v| v, |v| v, 0;
+    f32 => F32, f32::from_bits, f32::to_bits, 0.0;
+    f64 => F64, f64::from_bits, f64::to_bits, 0.0;
+    RefValue => Ref, |v| v, |v| v, RefValue::Null(RefType::FuncRef)
+}
@@ -1,18 +1,38 @@
 use crate::{
-    addressable::{Addr, Slot, Slottable},
+    addressable::Addr,
+    caller::Caller,
     frame::Frame,
-    instance::{Func, Index},
-    instr::Instr,
+    instance::{Func, Instance},
+    instr::{Instr, Kind},
     label::Label,
+    num::{self, Float, Integer},
     stack::Stack,
-    val::{RefValue, Value},
+    store::Store,
+    trap::{FrameInfo, Trap, TrapCode},
+    val::{FromValue, RefType, RefValue, Value, ValueType},
 };
-use anyhow::{Context, Result};
-use wasmparser::InitExpr;
+use anyhow::{bail, Context, Result};
+use wasmparser::{InitExpr, MemoryImmediate, Type, TypeOrFuncType};
 
+// NB
+// Frames live on the heap so the limit only guards against runaway
+// recursion; it matches the order of magnitude of other engines.
+// The frames are counted on the store across the invocations nested
+// through host functions, which are further limited as each of them
+// takes room on the native stack
+const MAX_CALL_DEPTH: usize = 10_000;
+const MAX_NESTED_INVOCATIONS: usize = 100;
+
+#[derive(Default)]
 pub struct VM {
     ip: usize,
     stack: Stack<StackEntry>,
+    // The frame of the function being executed
+    frame: Option<Frame>,
+    // The number of frames of the invocation, including the executing one
+    depth: usize,
+    // Whether the invocation stopped because the store ran out of fuel
+    out_of_fuel: bool,
 }
 
 pub enum StackEntry {
@@ -21,26 +41,2335 @@ pub enum StackEntry {
     Label(Label),
 }
 
-/// Resolves a constant initializer expression to a runtime value
-pub fn resolve_constant_expr(expr: &InitExpr) -> Result<Value> {
-    let mut ops_reader = expr.get_operators_reader();
-    let op = ops_reader.read()?;
-    let instr = Instr::try_from(op)?;
-    instr
-        .const_value()
-        .with_context(|| format!("{:?} is not a constant instruction", instr))
+impl VM {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Invokes the function at the given address with the given arguments,
+    /// returning the values produced by the function
+    pub fn invoke(&mut self, store: &mut Store, addr: Addr, args: &[Value]) -> Result<Vec<Value>> {
+        let func = store.func(&addr)?;
+        let params = &func.ty().params;
+
+        if params.len() != args.len() {
+            bail!(
+                "Function {:?} expects {} arguments, got {}",
+                addr,
+                params.len(),
+                args.len()
+            );
+        }
+
+        for (param, arg) in params.iter().zip(args) {
+            let expected = ValueType::try_from(*param)?;
+            if expected != arg.ty() {
+                bail!("Type mismatch: expected {:?}, got {:?}", expected, arg.ty());
+            }
+        }
+
+        let (frames, invocations) = store.call_depth();
+        if frames >= MAX_CALL_DEPTH || invocations >= MAX_NESTED_INVOCATIONS {
+            return Err(Trap::new(TrapCode::StackExhaustion).into());
+        }
+
+        let frame = match func {
+            Func::Wasm(func) => Frame::new(addr, func, args.to_vec())?,
+            Func::Host(func) => {
+                let func = func.clone();
+                store.set_call_depth(frames, invocations + 1);
+                let results = func.call(Caller::new(store, addr.instance_index()), args);
+                store.set_call_depth(frames, invocations);
+                return results.map_err(Trap::host);
+            }
+        };
+
+        self.ip = 0;
+        self.stack = Stack::default();
+        self.frame = Some(frame);
+        self.depth = 1;
+        self.out_of_fuel = false;
+        self.stack.push(StackEntry::Label(Label::Ret));
+
+        self.execute(store)
+    }
+
+    /// Resumes an invocation which trapped because the store ran out of
+    /// fuel, once fuel has been added; the instruction which couldn't be
+    /// paid for is executed again. Invocations which ran out of fuel in
+    /// wasm code called back by a host function can't be resumed, as the
+    /// host function can't be re-entered where it stopped
+    pub fn resume(&mut self, store: &mut Store) -> Result<Vec<Value>> {
+        if !self.out_of_fuel {
+            bail!(
+                "Only invocations which ran out of fuel outside of host functions can be resumed"
+            );
+        }
+        self.out_of_fuel = false;
+        self.ip -= 1;
+
+        self.execute(store)
+    }
+
+    /// Runs the invocation, which counts as nested in the
+    /// invocations running on the native stack until it returns
+    fn execute(&mut self, store: &mut Store) -> Result<Vec<Value>> {
+        let (frames, invocations) = store.call_depth();
+        store.set_call_depth(frames, invocations + 1);
+        let results = self.run(store);
+        store.set_call_depth(frames, invocations);
+
+        results.map_err(|err| self.capture_backtrace(store, err))
+    }
+
+    /// Records the wasm frames on the call stack into the trap carried
+    /// by the error, appending them to the frames of any nested
+    /// invocation; other errors are returned untouched
+    fn capture_backtrace(&self, store: &Store, err: anyhow::Error) -> anyhow::Error {
+        let mut trap = match Trap::from_error(err) {
+            Ok(trap) => trap,
+            Err(err) => return err,
+        };
+
+        let callers = self.stack.iter().filter_map(|entry| match entry {
+            StackEntry::CallFrame(frame) => Some(frame),
+            _ => None,
+        });
+        // The instruction pointers point past the instructions being executed
+        let frames = self
+            .frame
+            .iter()
+            .map(|frame| (frame, self.ip))
+            .chain(callers.map(|frame| (frame, frame.ip())));
+        for (frame, ip) in frames {
+            let func = frame.func();
+            let name = store
+                .instance(func.instance_index())
+                .ok()
+                .and_then(|instance| instance.names().func(func.element_index()))
+                .map(str::to_string);
+            let offset = frame.body().offset(ip.saturating_sub(1)).unwrap_or(0);
+            trap.push_frame(FrameInfo::new(
+                func.instance_index(),
+                func.element_index(),
+                name,
+                offset,
+            ));
+        }
+
+        trap.into()
+    }
+
+    fn run(&mut self, store: &mut Store) -> Result<Vec<Value>> {
+        loop {
+            let body = self.frame()?.body();
+            let instr = body
+                .instr(self.ip)
+                .with_context(|| format!("Instruction pointer {} out of bounds", self.ip))?;
+            self.ip += 1;
+
+            // NB
+            // Fuel is charged before the instruction has any effect,
+            // so that running out of fuel leaves the store untouched
+            if let Err(code) = store.charge_fuel(instr.kind()) {
+                self.out_of_fuel = true;
+                return Err(code.into());
+            }
+
+            match instr.kind() {
+                Kind::Unreachable => return Err(TrapCode::Unreachable.into()),
+                Kind::Nop => (),
+
+                Kind::Block { ty } => {
+                    let (params, results) = self.block_type(store, ty)?;
+                    let continuation = body.end_of(self.ip - 1)? + 1;
+                    self.enter(Label::Block(results, continuation), params)?;
+                }
+                Kind::Loop { ty } => {
+                    let (params, _) = self.block_type(store, ty)?;
+                    self.enter(Label::Loop(params, self.ip - 1), params)?;
+                }
+                Kind::If { ty } => {
+                    let cond: i32 = self.pop()?;
+                    let (params, results) = self.block_type(store, ty)?;
+                    let index = self.ip - 1;
+                    let end = body.end_of(index)?;
+                    self.enter(Label::If(results, end + 1), params)?;
+
+                    if cond == 0 {
+                        self.ip = match body.else_of(index)? {
+                            // Skip the else instruction and execute the else branch
+                            Some(else_index) => else_index + 1,
+                            // Let the end instruction exit the label
+                            None => end,
+                        };
+                    }
+                }
+                Kind::Else => {
+                    // The then branch of an if instruction completed;
+                    // let the end instruction exit the label
+                    self.ip = body.end_of(self.ip - 1)?;
+                }
+                Kind::End => {
+                    if let Some(results) = self.end()? {
+                        return Ok(results);
+                    }
+                }
+
+                Kind::Br { relative_depth } => {
+                    if let Some(results) = self.br(*relative_depth)? {
+                        return Ok(results);
+                    }
+                }
+                Kind::BrIf { relative_depth } => {
+                    let cond: i32 = self.pop()?;
+                    if cond != 0 {
+                        if let Some(results) = self.br(*relative_depth)? {
+                            return Ok(results);
+                        }
+                    }
+                }
+                Kind::Return => {
+                    if let Some(results) = self.r#return()? {
+                        return Ok(results);
+                    }
+                }
+                Kind::Call { function_index } => {
+                    let instance_index = self.frame()?.func().instance_index();
+                    let addr = store.instance(instance_index)?.func_addr(*function_index)?;
+                    self.call(store, addr)?;
+                }
+                Kind::CallIndirect { index, table_index } => {
+                    let addr = self.resolve_indirect(store, *index, *table_index)?;
+                    self.call(store, addr)?;
+                }
+                Kind::BrTable { table } => {
+                    let index: i32 = self.pop()?;
+                    if let Some(results) = self.br(table.target(index as u32))? {
+                        return Ok(results);
+                    }
+                }
+
+                Kind::Drop => {
+                    self.pop_value()?;
+                }
+                Kind::Select | Kind::TypedSelect { .. } => {
+                    let cond: i32 = self.pop()?;
+                    let val2 = self.pop_value()?;
+                    let val1 = self.pop_value()?;
+                    self.push(if cond != 0 { val1 } else { val2 });
+                }
+
+                Kind::LocalGet { local_index } => {
+                    let val = self.frame()?.local(*local_index)?;
+                    self.push(val);
+                }
+                Kind::LocalSet { local_index } => {
+                    let val = self.pop_value()?;
+                    self.frame_mut()?.set_local(*local_index, val)?;
+                }
+                Kind::LocalTee { local_index } => {
+                    let val = self.pop_value()?;
+                    self.frame_mut()?.set_local(*local_index, val)?;
+                    self.push(val);
+                }
+                Kind::GlobalGet { global_index } => {
+                    let addr = self.global_addr(store, *global_index)?;
+                    let val = store.global(&addr)?.value();
+                    self.push(val);
+                }
+                Kind::GlobalSet { global_index } => {
+                    let addr = self.global_addr(store, *global_index)?;
+                    let val = self.pop_value()?;
+                    store.global_mut(&addr)?.set(val);
+                }
+
+                // NB
+                // The alignment of a memory immediate is only a hint,
+                // unaligned accesses are allowed
+                Kind::I32Load { memarg } => self.load(store, memarg, i32::from_le_bytes)?,
+                Kind::I64Load { memarg } => self.load(store, memarg, i64::from_le_bytes)?,
+                Kind::F32Load { memarg } => {
+                    self.load(store, memarg, |b| Value::F32(u32::from_le_bytes(b)))?
+                }
+                Kind::F64Load { memarg } => {
+                    self.load(store, memarg, |b| Value::F64(u64::from_le_bytes(b)))?
+                }
+                Kind::I32Load8S { memarg } => {
+                    self.load(store, memarg, |b| i8::from_le_bytes(b) as i32)?
+                }
+                Kind::I32Load8U { memarg } => {
+                    self.load(store, memarg, |b| u8::from_le_bytes(b) as i32)?
+                }
+                Kind::I32Load16S { memarg } => {
+                    self.load(store, memarg, |b| i16::from_le_bytes(b) as i32)?
+                }
+                Kind::I32Load16U { memarg } => {
+                    self.load(store, memarg, |b| u16::from_le_bytes(b) as i32)?
+                }
+                Kind::I64Load8S { memarg } => {
+                    self.load(store, memarg, |b| i8::from_le_bytes(b) as i64)?
+                }
+                Kind::I64Load8U { memarg } => {
+                    self.load(store, memarg, |b| u8::from_le_bytes(b) as i64)?
+                }
+                Kind::I64Load16S { memarg } => {
+                    self.load(store, memarg, |b| i16::from_le_bytes(b) as i64)?
+                }
+                Kind::I64Load16U { memarg } => {
+                    self.load(store, memarg, |b| u16::from_le_bytes(b) as i64)?
+                }
+                Kind::I64Load32S { memarg } => {
+                    self.load(store, memarg, |b| i32::from_le_bytes(b) as i64)?
+                }
+                Kind::I64Load32U { memarg } => {
+                    self.load(store, memarg, |b| u32::from_le_bytes(b) as i64)?
+                }
+                Kind::I32Store { memarg } => self.store(store, memarg, i32::to_le_bytes)?,
+                Kind::I64Store { memarg } => self.store(store, memarg, i64::to_le_bytes)?,
+                Kind::F32Store { memarg } => self.store(store, memarg, |v: f32| v.to_le_bytes())?,
+                Kind::F64Store { memarg } => self.store(store, memarg, |v: f64| v.to_le_bytes())?,
+                Kind::I32Store8 { memarg } => {
+                    self.store(store, memarg, |v: i32| (v as u8).to_le_bytes())?
+                }
+                Kind::I32Store16 { memarg } => {
+                    self.store(store, memarg, |v: i32| (v as u16).to_le_bytes())?
+                }
+                Kind::I64Store8 { memarg } => {
+                    self.store(store, memarg, |v: i64| (v as u8).to_le_bytes())?
+                }
+                Kind::I64Store16 { memarg } => {
+                    self.store(store, memarg, |v: i64| (v as u16).to_le_bytes())?
+                }
+                Kind::I64Store32 { memarg } => {
+                    self.store(store, memarg, |v: i64| (v as u32).to_le_bytes())?
+                }
+
+                Kind::MemorySize { mem, .. } => {
+                    let addr = self.memory_addr(store, *mem)?;
+                    let size = store.memory(&addr)?.size();
+                    self.push(size as i32);
+                }
+                Kind::MemoryGrow { mem, .. } => {
+                    let addr = self.memory_addr(store, *mem)?;
+                    let delta: i32 = self.pop()?;
+                    let result = match store.grow_memory(&addr, delta as u32 as u64)? {
+                        Some(size) => size as i32,
+                        None => -1,
+                    };
+                    self.push(result);
+                }
+                Kind::MemoryCopy { src, dst } => {
+                    let dst_addr = self.memory_addr(store, *dst)?;
+                    let src_addr = self.memory_addr(store, *src)?;
+                    let len: i32 = self.pop()?;
+                    let src: i32 = self.pop()?;
+                    let dst: i32 = self.pop()?;
+                    store.copy_memory(
+                        &dst_addr,
+                        &src_addr,
+                        dst as u32 as u64,
+                        src as u32 as u64,
+                        len as u32 as u64,
+                    )?;
+                }
+                Kind::MemoryFill { mem } => {
+                    let addr = self.memory_addr(store, *mem)?;
+                    let len: i32 = self.pop()?;
+                    let val: i32 = self.pop()?;
+                    let dst: i32 = self.pop()?;
+                    store.memory_mut(&addr)?.fill(
+                        dst as u32 as u64,
+                        val as u8,
+                        len as u32 as u64,
+                    )?;
+                }
+                Kind::MemoryInit { segment, mem } => {
+                    let memory_addr = self.memory_addr(store, *mem)?;
+                    let data_addr = self.data_addr(store, *segment)?;
+                    let len: i32 = self.pop()?;
+                    let src: i32 = self.pop()?;
+                    let dst: i32 = self.pop()?;
+                    store.init_memory(
+                        &memory_addr,
+                        &data_addr,
+                        dst as u32 as u64,
+                        src as u32 as u64,
+                        len as u32 as u64,
+                    )?;
+                }
+                Kind::DataDrop { segment } => {
+                    let addr = self.data_addr(store, *segment)?;
+                    store.data_mut(&addr)?.clear();
+                }
+
+                Kind::TableGet { table } => {
+                    let addr = self.table_addr(store, *table)?;
+                    let index: i32 = self.pop()?;
+                    let val = store
+                        .table(&addr)?
+                        .get(index as u32)
+                        .ok_or(TrapCode::TableOutOfBounds)?;
+                    self.push(val);
+                }
+                Kind::TableSet { table } => {
+                    let addr = self.table_addr(store, *table)?;
+                    let val: RefValue = self.pop()?;
+                    let index: i32 = self.pop()?;
+                    store.table_mut(&addr)?.set(index as u32, val)?;
+                }
+                Kind::TableSize { table } => {
+                    let addr = self.table_addr(store, *table)?;
+                    let size = store.table(&addr)?.size();
+                    self.push(size as i32);
+                }
+                Kind::TableGrow { table } => {
+                    let addr = self.table_addr(store, *table)?;
+                    let delta: i32 = self.pop()?;
+                    let init: RefValue = self.pop()?;
+                    let result = match store.grow_table(&addr, delta as u32, init)? {
+                        Some(size) => size as i32,
+                        None => -1,
+                    };
+                    self.push(result);
+                }
+                Kind::TableFill { table } => {
+                    let addr = self.table_addr(store, *table)?;
+                    let len: i32 = self.pop()?;
+                    let val: RefValue = self.pop()?;
+                    let index: i32 = self.pop()?;
+                    store
+                        .table_mut(&addr)?
+                        .fill(index as u32, val, len as u32)?;
+                }
+                Kind::TableCopy {
+                    dst_table,
+                    src_table,
+                } => {
+                    let dst_addr = self.table_addr(store, *dst_table)?;
+                    let src_addr = self.table_addr(store, *src_table)?;
+                    let len: i32 = self.pop()?;
+                    let src: i32 = self.pop()?;
+                    let dst: i32 = self.pop()?;
+                    store.copy_table(&dst_addr, &src_addr, dst as u32, src as u32, len as u32)?;
+                }
+                Kind::TableInit { segment, table } => {
+                    let table_addr = self.table_addr(store, *table)?;
+                    let elem_addr = self.elem_addr(store, *segment)?;
+                    let len: i32 = self.pop()?;
+                    let src: i32 = self.pop()?;
+                    let dst: i32 = self.pop()?;
+                    store.init_table(
+                        &table_addr,
+                        &elem_addr,
+                        dst as u32,
+                        src as u32,
+                        len as u32,
+                    )?;
+                }
+                Kind::ElemDrop { segment } => {
+                    let addr = self.elem_addr(store, *segment)?;
+                    store.elem_mut(&addr)?.clear();
+                }
+
+                Kind::RefNull { ty } => self.push(RefValue::Null(RefType::try_from(*ty)?)),
+                Kind::RefIsNull => {
+                    let val: RefValue = self.pop()?;
+                    self.push(matches!(val, RefValue::Null(_)) as i32);
+                }
+                Kind::RefFunc { function_index } => {
+                    let instance_index = self.frame()?.func().instance_index();
+                    let addr = store.instance(instance_index)?.func_addr(*function_index)?;
+                    self.push(RefValue::FuncRef(addr));
+                }
+
+                Kind::I32Const { value } => self.push(*value),
+                Kind::I64Const { value } => self.push(*value),
+                Kind::F32Const { value } => self.push(Value::F32(value.bits())),
+                Kind::F64Const { value } => self.push(Value::F64(value.bits())),
+
+                Kind::I32Eqz => self.unop(|a: i32| i32::from(a == 0))?,
+                Kind::I32Eq => self.binop(|a: i32, b: i32| i32::from(a == b))?,
+                Kind::I32Ne => self.binop(|a: i32, b: i32| i32::from(a != b))?,
+                Kind::I32LtS => self.binop(|a: i32, b: i32| i32::from(a < b))?,
+                Kind::I32LtU => self.binop(|a: i32, b: i32| i32::from((a as u32) < (b as u32)))?,
+                Kind::I32GtS => self.binop(|a: i32, b: i32| i32::from(a > b))?,
+                Kind::I32GtU => self.binop(|a: i32, b: i32| i32::from((a as u32) > (b as u32)))?,
+                Kind::I32LeS => self.binop(|a: i32, b: i32| i32::from(a <= b))?,
+                Kind::I32LeU => self.binop(|a: i32, b: i32| i32::from((a as u32) <= (b as u32)))?,
+                Kind::I32GeS => self.binop(|a: i32, b: i32| i32::from(a >= b))?,
+                Kind::I32GeU => self.binop(|a: i32, b: i32| i32::from((a as u32) >= (b as u32)))?,
+
+                Kind::I64Eqz => self.unop(|a: i64| i32::from(a == 0))?,
+                Kind::I64Eq => self.binop(|a: i64, b: i64| i32::from(a == b))?,
+                Kind::I64Ne => self.binop(|a: i64, b: i64| i32::from(a != b))?,
+                Kind::I64LtS => self.binop(|a: i64, b: i64| i32::from(a < b))?,
+                Kind::I64LtU => self.binop(|a: i64, b: i64| i32::from((a as u64) < (b as u64)))?,
+                Kind::I64GtS => self.binop(|a: i64, b: i64| i32::from(a > b))?,
+                Kind::I64GtU => self.binop(|a: i64, b: i64| i32::from((a as u64) > (b as u64)))?,
+                Kind::I64LeS => self.binop(|a: i64, b: i64| i32::from(a <= b))?,
+                Kind::I64LeU => self.binop(|a: i64, b: i64| i32::from((a as u64) <= (b as u64)))?,
+                Kind::I64GeS => self.binop(|a: i64, b: i64| i32::from(a >= b))?,
+                Kind::I64GeU => self.binop(|a: i64, b: i64| i32::from((a as u64) >= (b as u64)))?,
+
+                Kind::F32Eq => self.binop(|a: f32, b: f32| i32::from(a == b))?,
+                Kind::F32Ne => self.binop(|a: f32, b: f32| i32::from(a != b))?,
+                Kind::F32Lt => self.binop(|a: f32, b: f32| i32::from(a < b))?,
+                Kind::F32Gt => self.binop(|a: f32, b: f32| i32::from(a > b))?,
+                Kind::F32Le => self.binop(|a: f32, b: f32| i32::from(a <= b))?,
+                Kind::F32Ge => self.binop(|a: f32, b: f32| i32::from(a >= b))?,
+
+                Kind::F64Eq => self.binop(|a: f64, b: f64| i32::from(a == b))?,
+                Kind::F64Ne => self.binop(|a: f64, b: f64| i32::from(a != b))?,
+                Kind::F64Lt => self.binop(|a: f64, b: f64| i32::from(a < b))?,
+                Kind::F64Gt => self.binop(|a: f64, b: f64| i32::from(a > b))?,
+                Kind::F64Le => self.binop(|a: f64, b: f64| i32::from(a <= b))?,
+                Kind::F64Ge => self.binop(|a: f64, b: f64| i32::from(a >= b))?,
+
+                Kind::I32Clz => self.unop(|a: i32| a.leading_zeros() as i32)?,
+                Kind::I32Ctz => self.unop(|a: i32| a.trailing_zeros() as i32)?,
+                Kind::I32Popcnt => self.unop(|a: i32| a.count_ones() as i32)?,
+                Kind::I32Add => self.binop(|a: i32, b: i32| a.wrapping_add(b))?,
+                Kind::I32Sub => self.binop(|a: i32, b: i32| a.wrapping_sub(b))?,
+                Kind::I32Mul => self.binop(|a: i32, b: i32| a.wrapping_mul(b))?,
+                Kind::I32DivS => self.try_binop(i32::div_s)?,
+                Kind::I32DivU => self.try_binop(i32::div_u)?,
+                Kind::I32RemS => self.try_binop(i32::rem_s)?,
+                Kind::I32RemU => self.try_binop(i32::rem_u)?,
+                Kind::I32And => self.binop(|a: i32, b: i32| a & b)?,
+                Kind::I32Or => self.binop(|a: i32, b: i32| a | b)?,
+                Kind::I32Xor => self.binop(|a: i32, b: i32| a ^ b)?,
+                // NB
+                // Shift and rotate counts are taken modulo the bit width
+                Kind::I32Shl => self.binop(|a: i32, b: i32| a.wrapping_shl(b as u32))?,
+                Kind::I32ShrS => self.binop(|a: i32, b: i32| a.wrapping_shr(b as u32))?,
+                Kind::I32ShrU => {
+                    self.binop(|a: i32, b: i32| (a as u32).wrapping_shr(b as u32) as i32)?
+                }
+                Kind::I32Rotl => self.binop(|a: i32, b: i32| a.rotate_left(b as u32))?,
+                Kind::I32Rotr => self.binop(|a: i32, b: i32| a.rotate_right(b as u32))?,
+
+                Kind::I64Clz => self.unop(|a: i64| a.leading_zeros() as i64)?,
+                Kind::I64Ctz => self.unop(|a: i64| a.trailing_zeros() as i64)?,
+                Kind::I64Popcnt => self.unop(|a: i64| a.count_ones() as i64)?,
+                Kind::I64Add => self.binop(|a: i64, b: i64| a.wrapping_add(b))?,
+                Kind::I64Sub => self.binop(|a: i64, b: i64| a.wrapping_sub(b))?,
+                Kind::I64Mul => self.binop(|a: i64, b: i64| a.wrapping_mul(b))?,
+                Kind::I64DivS => self.try_binop(i64::div_s)?,
+                Kind::I64DivU => self.try_binop(i64::div_u)?,
+                Kind::I64RemS => self.try_binop(i64::rem_s)?,
+                Kind::I64RemU => self.try_binop(i64::rem_u)?,
+                Kind::I64And => self.binop(|a: i64, b: i64| a & b)?,
+                Kind::I64Or => self.binop(|a: i64, b: i64| a | b)?,
+                Kind::I64Xor => self.binop(|a: i64, b: i64| a ^ b)?,
+                Kind::I64Shl => self.binop(|a: i64, b: i64| a.wrapping_shl(b as u32))?,
+                Kind::I64ShrS => self.binop(|a: i64, b: i64| a.wrapping_shr(b as u32))?,
+                Kind::I64ShrU => {
+                    self.binop(|a: i64, b: i64| (a as u64).wrapping_shr(b as u32) as i64)?
+                }
+                Kind::I64Rotl => self.binop(|a: i64, b: i64| a.rotate_left(b as u32))?,
+                Kind::I64Rotr => self.binop(|a: i64, b: i64| a.rotate_right(b as u32))?,
+
+                Kind::F32Abs => self.unop(|a: f32| a.abs())?,
+                Kind::F32Neg => self.unop(|a: f32| -a)?,
+                Kind::F32Ceil => self.unop(|a: f32| a.ceil().canonicalize())?,
+                Kind::F32Floor => self.unop(|a: f32| a.floor().canonicalize())?,
+                Kind::F32Trunc => self.unop(|a: f32| a.trunc().canonicalize())?,
+                Kind::F32Nearest => self.unop(|a: f32| a.nearest())?,
+                Kind::F32Sqrt => self.unop(|a: f32| a.sqrt().canonicalize())?,
+                Kind::F32Add => self.binop(|a: f32, b: f32| (a + b).canonicalize())?,
+                Kind::F32Sub => self.binop(|a: f32, b: f32| (a - b).canonicalize())?,
+                Kind::F32Mul => self.binop(|a: f32, b: f32| (a * b).canonicalize())?,
+                Kind::F32Div => self.binop(|a: f32, b: f32| (a / b).canonicalize())?,
+                Kind::F32Min => self.binop(|a: f32, b: f32| a.wasm_min(b))?,
+                Kind::F32Max => self.binop(|a: f32, b: f32| a.wasm_max(b))?,
+                Kind::F32Copysign => self.binop(|a: f32, b: f32| a.copysign(b))?,
+
+                Kind::F64Abs => self.unop(|a: f64| a.abs())?,
+                Kind::F64Neg => self.unop(|a: f64| -a)?,
+                Kind::F64Ceil => self.unop(|a: f64| a.ceil().canonicalize())?,
+                Kind::F64Floor => self.unop(|a: f64| a.floor().canonicalize())?,
+                Kind::F64Trunc => self.unop(|a: f64| a.trunc().canonicalize())?,
+                Kind::F64Nearest => self.unop(|a: f64| a.nearest())?,
+                Kind::F64Sqrt => self.unop(|a: f64| a.sqrt().canonicalize())?,
+                Kind::F64Add => self.binop(|a: f64, b: f64| (a + b).canonicalize())?,
+                Kind::F64Sub => self.binop(|a: f64, b: f64| (a - b).canonicalize())?,
+                Kind::F64Mul => self.binop(|a: f64, b: f64| (a * b).canonicalize())?,
+                Kind::F64Div => self.binop(|a: f64, b: f64| (a / b).canonicalize())?,
+                Kind::F64Min => self.binop(|a: f64, b: f64| a.wasm_min(b))?,
+                Kind::F64Max => self.binop(|a: f64, b: f64| a.wasm_max(b))?,
+                Kind::F64Copysign => self.binop(|a: f64, b: f64| a.copysign(b))?,
+
+                Kind::I32WrapI64 => self.unop(|a: i64| a as i32)?,
+                Kind::I32TruncF32S => self.try_unop(num::i32_trunc_f32_s)?,
+                Kind::I32TruncF32U => self.try_unop(num::i32_trunc_f32_u)?,
+                Kind::I32TruncF64S => self.try_unop(num::i32_trunc_f64_s)?,
+                Kind::I32TruncF64U => self.try_unop(num::i32_trunc_f64_u)?,
+                Kind::I64ExtendI32S => self.unop(|a: i32| a as i64)?,
+                Kind::I64ExtendI32U => self.unop(|a: i32| a as u32 as i64)?,
+                Kind::I64TruncF32S => self.try_unop(num::i64_trunc_f32_s)?,
+                Kind::I64TruncF32U => self.try_unop(num::i64_trunc_f32_u)?,
+                Kind::I64TruncF64S => self.try_unop(num::i64_trunc_f64_s)?,
+                Kind::I64TruncF64U => self.try_unop(num::i64_trunc_f64_u)?,
+                Kind::F32ConvertI32S => self.unop(|a: i32| a as f32)?,
+                Kind::F32ConvertI32U => self.unop(|a: i32| a as u32 as f32)?,
+                Kind::F32ConvertI64S => self.unop(|a: i64| a as f32)?,
+                Kind::F32ConvertI64U => self.unop(|a: i64| a as u64 as f32)?,
+                Kind::F32DemoteF64 => self.unop(|a: f64| (a as f32).canonicalize())?,
+                Kind::F64ConvertI32S => self.unop(|a: i32| a as f64)?,
+                Kind::F64ConvertI32U => self.unop(|a: i32| a as u32 as f64)?,
+                Kind::F64ConvertI64S => self.unop(|a: i64| a as f64)?,
+                Kind::F64ConvertI64U => self.unop(|a: i64| a as u64 as f64)?,
+                Kind::F64PromoteF32 => self.unop(|a: f32| (a as f64).canonicalize())?,
+                Kind::I32ReinterpretF32 => self.unop(|a: f32| a.to_bits() as i32)?,
+                Kind::I64ReinterpretF64 => self.unop(|a: f64| a.to_bits() as i64)?,
+                Kind::F32ReinterpretI32 => self.unop(|a: i32| f32::from_bits(a as u32))?,
+                Kind::F64ReinterpretI64 => self.unop(|a: i64| f64::from_bits(a as u64))?,
+
+                Kind::I32Extend8S => self.unop(|a: i32| a as i8 as i32)?,
+                Kind::I32Extend16S => self.unop(|a: i32| a as i16 as i32)?,
+                Kind::I64Extend8S => self.unop(|a: i64| a as i8 as i64)?,
+                Kind::I64Extend16S => self.unop(|a: i64| a as i16 as i64)?,
+                Kind::I64Extend32S => self.unop(|a: i64| a as i32 as i64)?,
+
+                // NB
+                // Float to integer casts saturate and map NaN to 0
+                // which are the semantics of the non-trapping truncation
+                Kind::I32TruncSatF32S => self.unop(|a: f32| a as i32)?,
+                Kind::I32TruncSatF32U => self.unop(|a: f32| a as u32 as i32)?,
+                Kind::I32TruncSatF64S => self.unop(|a: f64| a as i32)?,
+                Kind::I32TruncSatF64U => self.unop(|a: f64| a as u32 as i32)?,
+                Kind::I64TruncSatF32S => self.unop(|a: f32| a as i64)?,
+                Kind::I64TruncSatF32U => self.unop(|a: f32| a as u64 as i64)?,
+                Kind::I64TruncSatF64S => self.unop(|a: f64| a as i64)?,
+                Kind::I64TruncSatF64U => self.unop(|a: f64| a as u64 as i64)?,
+
+                kind => bail!("{:?} not supported", kind),
+            }
+        }
+    }
+
+    /// Resolves the number of parameters and results of a block type
+    fn block_type(&self, store: &Store, ty: &TypeOrFuncType) -> Result<(usize, usize)> {
+        match ty {
+            TypeOrFuncType::Type(Type::EmptyBlockType) => Ok((0, 0)),
+            TypeOrFuncType::Type(_) => Ok((0, 1)),
+            TypeOrFuncType::FuncType(index) => {
+                let instance_index = self.frame()?.func().instance_index();
+                let ty = store
+                    .instance(instance_index)?
+                    .types()
+                    .get(*index as usize)
+                    .with_context(|| format!("Invalid type index {}", index))?;
+
+                Ok((ty.params.len(), ty.returns.len()))
+            }
+        }
+    }
+
+    /// Enters a structured control instruction, pushing its label
+    /// beneath the parameters of the block
+    fn enter(&mut self, label: Label, params: usize) -> Result<()> {
+        let vals = self.pop_values(params)?;
+        self.stack.push(StackEntry::Label(label));
+        self.push_values(vals);
+
+        Ok(())
+    }
+
+    /// Exits the innermost label, keeping the values on top of it;
+    /// returns the results of the invocation once the outermost
+    /// function body is exited
+    fn end(&mut self) -> Result<Option<Vec<Value>>> {
+        let (label, mut vals) = self.unwind(0)?;
+        vals.reverse();
+
+        match label {
+            Label::Ret => self.ret(vals),
+            _ => {
+                self.push_values(vals);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Branches to the label at the given depth, carrying as many values
+    /// as the arity of the label
+    fn br(&mut self, depth: u32) -> Result<Option<Vec<Value>>> {
+        let (label, mut vals) = self.unwind(depth)?;
+        let arity = match label {
+            Label::Block(arity, _) | Label::If(arity, _) | Label::Loop(arity, _) => arity,
+            Label::Ret => self.frame()?.arity(),
+        };
+
+        if vals.len() < arity {
+            bail!(
+                "Expected {} values on the stack when branching, found {}",
+                arity,
+                vals.len()
+            );
+        }
+        vals.truncate(arity);
+        vals.reverse();
+
+        match label {
+            Label::Ret => return self.ret(vals),
+            Label::Block(_, continuation)
+            | Label::If(_, continuation)
+            | Label::Loop(_, continuation) => self.ip = continuation,
+        }
+        self.push_values(vals);
+
+        Ok(None)
+    }
+
+    /// Calls the function at the given address, taking its
+    /// arguments from the stack
+    fn call(&mut self, store: &mut Store, addr: Addr) -> Result<()> {
+        let func = match store.func(&addr)? {
+            Func::Wasm(func) => func,
+            Func::Host(func) => {
+                let func = func.clone();
+                let args = self.pop_values(func.ty().params.len())?;
+                let instance_index = self.frame()?.func().instance_index();
+                // Invocations nested in the host function are
+                // nested in the frames of this invocation
+                let (frames, invocations) = store.call_depth();
+                store.set_call_depth(frames + self.depth, invocations);
+                let results = func.call(Caller::new(store, instance_index), &args);
+                store.set_call_depth(frames, invocations);
+                self.push_values(results.map_err(Trap::host)?);
+
+                return Ok(());
+            }
+        };
+        if store.call_depth().0 + self.depth >= MAX_CALL_DEPTH {
+            return Err(TrapCode::StackExhaustion.into());
+        }
+        let args = self.pop_values(func.ty().params.len())?;
+        let frame = Frame::new(addr, func, args)?;
+
+        let mut caller = self.frame.replace(frame).context("No active frame")?;
+        caller.set_ip(self.ip);
+        self.stack.push(StackEntry::CallFrame(caller));
+        self.stack.push(StackEntry::Label(Label::Ret));
+        self.ip = 0;
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    /// Resolves the callee of an indirect call through the table at the
+    /// given index, checking its type against the expected type index
+    fn resolve_indirect(&mut self, store: &Store, index: u32, table_index: u32) -> Result<Addr> {
+        let elem_index: i32 = self.pop()?;
+        let instance = store.instance(self.frame()?.func().instance_index())?;
+        let table = store.table(&instance.table_addr(table_index)?)?;
+
+        let addr = match table.get(elem_index as u32) {
+            None => return Err(TrapCode::TableOutOfBounds.into()),
+            Some(RefValue::FuncRef(addr)) => addr,
+            Some(_) => return Err(TrapCode::NullReference.into()),
+        };
+
+        let expected = instance
+            .types()
+            .get(index as usize)
+            .with_context(|| format!("Invalid type index {}", index))?;
+        if store.func(&addr)?.ty() != expected {
+            return Err(TrapCode::IndirectCallTypeMismatch.into());
+        }
+
+        Ok(addr)
+    }
+
+    /// Exits the current function from any nesting depth
+    fn r#return(&mut self) -> Result<Option<Vec<Value>>> {
+        let results = self.pop_values(self.frame()?.arity())?;
+        loop {
+            match self.stack.pop()? {
+                StackEntry::Label(Label::Ret) => return self.ret(results),
+                StackEntry::CallFrame(_) => bail!("Unexpected call frame while returning"),
+                _ => (),
+            }
+        }
+    }
+
+    /// Returns from the current function with the given results,
+    /// once its body label has been exited; the results of the
+    /// invocation are returned once the outermost function returns
+    fn ret(&mut self, results: Vec<Value>) -> Result<Option<Vec<Value>>> {
+        if self.stack.is_empty() {
+            self.frame = None;
+            return Ok(Some(results));
+        }
+
+        match self.stack.pop()? {
+            StackEntry::CallFrame(caller) => {
+                self.ip = caller.ip();
+                self.frame = Some(caller);
+                self.depth -= 1;
+                self.push_values(results);
+
+                Ok(None)
+            }
+            _ => bail!("Expected the frame of the caller on the stack"),
+        }
+    }
+
+    /// Pops entries up to and including the label at the given depth,
+    /// returning the label and the values found on the way, starting
+    /// from the top of the stack
+    fn unwind(&mut self, depth: u32) -> Result<(Label, Vec<Value>)> {
+        let mut vals = vec![];
+        let mut depth = depth;
+        loop {
+            match self.stack.pop()? {
+                StackEntry::Val(v) => vals.push(v),
+                StackEntry::Label(label) => {
+                    if depth == 0 {
+                        return Ok((label, vals));
+                    }
+                    depth -= 1;
+                }
+                StackEntry::CallFrame(_) => bail!("Unexpected call frame while exiting a label"),
+            }
+        }
+    }
+
+    fn frame(&self) -> Result<&Frame> {
+        self.frame.as_ref().context("No active frame")
+    }
+
+    fn frame_mut(&mut self) -> Result<&mut Frame> {
+        self.frame.as_mut().context("No active frame")
+    }
+
+    fn push<T: Into<Value>>(&mut self, val: T) {
+        self.stack.push(StackEntry::Val(val.into()));
+    }
+
+    fn pop_value(&mut self) -> Result<Value> {
+        match self.stack.pop()? {
+            StackEntry::Val(v) => Ok(v),
+            _ => bail!("Expected a value on top of the stack"),
+        }
+    }
+
+    fn pop<T: FromValue>(&mut self) -> Result<T> {
+        Ok(T::from_value(self.pop_value()?))
+    }
+
+    /// Applies a unary operator to the value on top of the stack
+    fn unop<T, R>(&mut self, op: impl FnOnce(T) -> R) -> Result<()>
+    where
+        T: FromValue,
+        R: Into<Value>,
+    {
+        let a = self.pop()?;
+        self.push(op(a));
+
+        Ok(())
+    }
+
+    /// Applies a unary operator which may trap to the value on top of the stack
+    fn try_unop<T, R>(&mut self, op: impl FnOnce(T) -> Result<R, TrapCode>) -> Result<()>
+    where
+        T: FromValue,
+        R: Into<Value>,
+    {
+        let a = self.pop()?;
+        self.push(op(a)?);
+
+        Ok(())
+    }
+
+    /// Applies a binary operator to the two values on top of the stack
+    fn binop<T, R>(&mut self, op: impl FnOnce(T, T) -> R) -> Result<()>
+    where
+        T: FromValue,
+        R: Into<Value>,
+    {
+        self.try_binop(|a, b| Ok(op(a, b)))
+    }
+
+    /// Applies a binary operator which may trap to the two values
+    /// on top of the stack
+    fn try_binop<T, R>(&mut self, op: impl FnOnce(T, T) -> Result<R, TrapCode>) -> Result<()>
+    where
+        T: FromValue,
+        R: Into<Value>,
+    {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        self.push(op(a, b)?);
+
+        Ok(())
+    }
+
+    /// Resolves the address of a global of the current instance
+    fn global_addr(&self, store: &Store, index: u32) -> Result<Addr> {
+        let instance_index = self.frame()?.func().instance_index();
+        store.instance(instance_index)?.global_addr(index)
+    }
+
+    /// Resolves the address of a table of the current instance
+    fn table_addr(&self, store: &Store, index: u32) -> Result<Addr> {
+        let instance_index = self.frame()?.func().instance_index();
+        store.instance(instance_index)?.table_addr(index)
+    }
+
+    /// Resolves the address of an element segment of the current instance
+    fn elem_addr(&self, store: &Store, index: u32) -> Result<Addr> {
+        let instance_index = self.frame()?.func().instance_index();
+        Ok(store.instance(instance_index)?.elem_addr(index))
+    }
+
+    /// Resolves the address of a data segment of the current instance
+    fn data_addr(&self, store: &Store, index: u32) -> Result<Addr> {
+        let instance_index = self.frame()?.func().instance_index();
+        Ok(store.instance(instance_index)?.data_addr(index))
+    }
+
+    /// Resolves the address of a memory of the current instance
+    fn memory_addr(&self, store: &Store, index: u32) -> Result<Addr> {
+        let instance_index = self.frame()?.func().instance_index();
+        store.instance(instance_index)?.memory_addr(index)
+    }
+
+    /// Pops an address and computes the effective address of a memory access
+    fn effective_addr(&mut self, memarg: &MemoryImmediate) -> Result<u64> {
+        let base: i32 = self.pop()?;
+        Ok(base as u32 as u64 + memarg.offset)
+    }
+
+    /// Loads `N` bytes from memory, decoding them into a value
+    fn load<const N: usize, R: Into<Value>>(
+        &mut self,
+        store: &Store,
+        memarg: &MemoryImmediate,
+        decode: impl FnOnce([u8; N]) -> R,
+    ) -> Result<()> {
+        let addr = self.memory_addr(store, memarg.memory)?;
+        let ea = self.effective_addr(memarg)?;
+        let bytes = store.memory(&addr)?.read::<N>(ea)?;
+        self.push(decode(bytes));
+
+        Ok(())
+    }
+
+    /// Stores the value on top of the stack in memory, encoded as `N` bytes
+    fn store<const N: usize, T: FromValue>(
+        &mut self,
+        store: &mut Store,
+        memarg: &MemoryImmediate,
+        encode: impl FnOnce(T) -> [u8; N],
+    ) -> Result<()> {
+        let addr = self.memory_addr(store, memarg.memory)?;
+        let val = self.pop()?;
+        let ea = self.effective_addr(memarg)?;
+        store.memory_mut(&addr)?.write(ea, &encode(val))?;
+
+        Ok(())
+    }
+
+    /// Pops the given number of values, in the order they were pushed
+    fn pop_values(&mut self, count: usize) -> Result<Vec<Value>> {
+        let mut vals = (0..count)
+            .map(|_| self.pop_value())
+            .collect::<Result<Vec<_>>>()?;
+        vals.reverse();
+
+        Ok(vals)
+    }
+
+    fn push_values(&mut self, vals: Vec<Value>) {
+        for val in vals {
+            self.push(val);
+        }
+    }
 }
 
-/// Resolves a funcref initializer expr
-pub fn resolve_funcref_expr(expr: &InitExpr, instance_index: Index) -> Result<RefValue> {
+/// Resolves a constant initializer expression to a runtime value;
+/// `globals` holds the values of the imported globals, which are the
+/// only ones constant expressions can refer to, and function
+/// references are resolved against the functions of the instance
+pub fn resolve_constant_expr(
+    expr: &InitExpr,
+    globals: &[Value],
+    instance: &Instance,
+) -> Result<Value> {
     let mut ops_reader = expr.get_operators_reader();
-    let op = ops_reader.read()?;
-    let instr = Instr::try_from(op)?;
-    let func_idx = instr
-        .funcref_idx()
-        .with_context(|| format!("{:?} is not a function reference", instr))?;
+    let mut stack = vec![];
+    loop {
+        let instr = Instr::try_from(ops_reader.read()?)?;
+        let val = match instr.kind() {
+            Kind::End => break,
+            Kind::GlobalGet { global_index } => globals
+                .get(*global_index as usize)
+                .copied()
+                .with_context(|| format!("Invalid imported global index {}", global_index))?,
+            Kind::RefNull { ty } => Value::Ref(RefValue::Null(RefType::try_from(*ty)?)),
+            Kind::RefFunc { function_index } => {
+                Value::Ref(RefValue::FuncRef(instance.func_addr(*function_index)?))
+            }
+            // The arithmetic of the extended constant expressions proposal
+            Kind::I32Add
+            | Kind::I32Sub
+            | Kind::I32Mul
+            | Kind::I64Add
+            | Kind::I64Sub
+            | Kind::I64Mul => {
+                let b = stack.pop();
+                let a = stack.pop();
+                match (instr.kind(), a, b) {
+                    (Kind::I32Add, Some(Value::I32(a)), Some(Value::I32(b))) => {
+                        Value::I32(a.wrapping_add(b))
+                    }
+                    (Kind::I32Sub, Some(Value::I32(a)), Some(Value::I32(b))) => {
+                        Value::I32(a.wrapping_sub(b))
+                    }
+                    (Kind::I32Mul, Some(Value::I32(a)), Some(Value::I32(b))) => {
+                        Value::I32(a.wrapping_mul(b))
+                    }
+                    (Kind::I64Add, Some(Value::I64(a)), Some(Value::I64(b))) => {
+                        Value::I64(a.wrapping_add(b))
+                    }
+                    (Kind::I64Sub, Some(Value::I64(a)), Some(Value::I64(b))) => {
+                        Value::I64(a.wrapping_sub(b))
+                    }
+                    (Kind::I64Mul, Some(Value::I64(a)), Some(Value::I64(b))) => {
+                        Value::I64(a.wrapping_mul(b))
+                    }
+                    (_, a, b) => bail!("Invalid operands {:?} and {:?} for {:?}", a, b, instr),
+                }
+            }
+            _ => instr
+                .const_value()
+                .with_context(|| format!("{:?} is not a constant instruction", instr))?,
+        };
+        stack.push(val);
+    }
+
+    match stack.as_slice() {
+        [val] => Ok(*val),
+        _ => bail!("Constant expression produced {} values", stack.len()),
+    }
+}
+
+/// Resolves the constant expression of an element segment item
+/// to a reference
+pub fn resolve_ref_expr(
+    expr: &InitExpr,
+    globals: &[Value],
+    instance: &Instance,
+) -> Result<RefValue> {
+    match resolve_constant_expr(expr, globals, instance)? {
+        Value::Ref(val) => Ok(val),
+        val => bail!("{:?} is not a reference", val),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_constant_expr, MAX_CALL_DEPTH, MAX_NESTED_INVOCATIONS, VM};
+    use crate::{
+        addressable::{Addr, Slot},
+        caller::Caller,
+        config::Config,
+        instance::Instance,
+        limiter::ResourceLimiter,
+        linker::Linker,
+        module::Module,
+        store::Store,
+        trap::{Trap, TrapCode},
+        val::Value,
+    };
+    use anyhow::Result;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn invoke(wat: &str, args: &[Value]) -> Result<Vec<Value>> {
+        invoke_nth(wat, 0, args)
+    }
+
+    /// Invokes the function at the given index of the module
+    fn invoke_nth(wat: &str, index: u32, args: &[Value]) -> Result<Vec<Value>> {
+        let bytes = wat::parse_str(wat)?;
+        let module = Module::from_binary(&bytes)?;
+        let mut store = Store::new();
+        let instance = store.instantiate(&module, None)?;
+        let addr = Addr::new_unsafe(instance.index(), index, Slot::Func);
+
+        VM::new().invoke(&mut store, addr, args)
+    }
+
+    #[test]
+    fn constants() {
+        let results = invoke(
+            r#"(module
+                 (func (result i32 i64 f32 f64)
+                   i32.const 1
+                   i64.const -2
+                   f32.const 1.5
+                   f64.const -0.25))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                Value::I32(1),
+                Value::I64(-2),
+                Value::F32(1.5f32.to_bits()),
+                Value::F64((-0.25f64).to_bits())
+            ]
+        );
+    }
+
+    #[test]
+    fn locals() {
+        let results = invoke(
+            r#"(module
+                 (func (param i32) (result i64 i64 i32)
+                   (local i64)
+                   local.get 1
+                   i64.const 7
+                   local.set 1
+                   local.get 0
+                   local.tee 0
+                   drop
+                   local.get 1
+                   local.get 0))"#,
+            &[Value::I32(42)],
+        )
+        .unwrap();
+
+        assert_eq!(results, vec![Value::I64(0), Value::I64(7), Value::I32(42)]);
+    }
+
+    #[test]
+    fn select() {
+        let wat = r#"(module
+                       (func (param i32) (result i32)
+                         i32.const 10
+                         i32.const 20
+                         local.get 0
+                         select))"#;
+
+        assert_eq!(invoke(wat, &[Value::I32(1)]).unwrap(), vec![Value::I32(10)]);
+        assert_eq!(invoke(wat, &[Value::I32(0)]).unwrap(), vec![Value::I32(20)]);
+    }
+
+    #[test]
+    fn unreachable() {
+        let err = invoke(r#"(module (func nop unreachable))"#, &[]).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::Unreachable)
+        );
+    }
+
+    #[test]
+    fn argument_mismatch() {
+        let wat = r#"(module (func (param i32)))"#;
+
+        assert!(invoke(wat, &[]).is_err());
+        assert!(invoke(wat, &[Value::I64(0)]).is_err());
+    }
+
+    #[test]
+    fn if_else() {
+        let wat = r#"(module
+                       (func (param i32) (result i32)
+                         local.get 0
+                         if (result i32)
+                           i32.const 1
+                         else
+                           i32.const 2
+                         end))"#;
+
+        assert_eq!(invoke(wat, &[Value::I32(5)]).unwrap(), vec![Value::I32(1)]);
+        assert_eq!(invoke(wat, &[Value::I32(0)]).unwrap(), vec![Value::I32(2)]);
+    }
+
+    #[test]
+    fn if_without_else() {
+        let wat = r#"(module
+                       (func (param i32) (result i32)
+                         i32.const 7
+                         local.get 0
+                         if (param i32) (result i32)
+                           drop
+                           i32.const 8
+                         end))"#;
+
+        assert_eq!(invoke(wat, &[Value::I32(1)]).unwrap(), vec![Value::I32(8)]);
+        assert_eq!(invoke(wat, &[Value::I32(0)]).unwrap(), vec![Value::I32(7)]);
+    }
+
+    #[test]
+    fn br_unwinds_operand_stack() {
+        let results = invoke(
+            r#"(module
+                 (func (result i32 i32)
+                   block (result i32 i32)
+                     i32.const 9
+                     block
+                       i32.const 10
+                       br 0
+                     end
+                     i32.const 1
+                     i32.const 2
+                     br 0
+                   end))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(results, vec![Value::I32(1), Value::I32(2)]);
+    }
+
+    #[test]
+    fn block_params() {
+        let results = invoke(
+            r#"(module
+                 (func (result i32 i32 i32)
+                   i32.const 1
+                   i32.const 2
+                   i32.const 3
+                   block (param i32 i32) (result i32 i32)
+                     drop
+                     i32.const 4
+                   end))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(results, vec![Value::I32(1), Value::I32(2), Value::I32(4)]);
+    }
+
+    #[test]
+    fn loop_and_br_if() {
+        // The loop runs twice: the first iteration sets the flag
+        // in local 0 and the second one copies it into local 1
+        let results = invoke(
+            r#"(module
+                 (func (result i32) (local i32 i32)
+                   loop
+                     local.get 0
+                     local.set 1
+                     i32.const 0
+                     i32.const 1
+                     local.get 0
+                     select
+                     i32.const 1
+                     local.set 0
+                     br_if 0
+                   end
+                   local.get 1))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(results, vec![Value::I32(1)]);
+    }
+
+    #[test]
+    fn br_table() {
+        let wat = r#"(module
+                       (func (param i32) (result i32) (local i32)
+                         block
+                           block
+                             block
+                               local.get 0
+                               br_table 0 1 2
+                             end
+                             i32.const 10
+                             local.set 1
+                             br 1
+                           end
+                           i32.const 20
+                           local.set 1
+                         end
+                         local.get 1))"#;
+
+        assert_eq!(invoke(wat, &[Value::I32(0)]).unwrap(), vec![Value::I32(10)]);
+        assert_eq!(invoke(wat, &[Value::I32(1)]).unwrap(), vec![Value::I32(20)]);
+        assert_eq!(invoke(wat, &[Value::I32(2)]).unwrap(), vec![Value::I32(0)]);
+        assert_eq!(invoke(wat, &[Value::I32(-1)]).unwrap(), vec![Value::I32(0)]);
+    }
+
+    #[test]
+    fn br_to_function_body() {
+        let results = invoke(
+            r#"(module
+                 (func (result i32)
+                   block
+                     i32.const 3
+                     br 1
+                   end
+                   i32.const 4))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(results, vec![Value::I32(3)]);
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        let wat = r#"(module
+                       (func (param i32) (result i32) (local i32)
+                         i32.const 1
+                         local.set 1
+                         block
+                           loop
+                             local.get 0
+                             i32.eqz
+                             br_if 1
+                             local.get 1
+                             local.get 0
+                             i32.mul
+                             local.set 1
+                             local.get 0
+                             i32.const 1
+                             i32.sub
+                             local.set 0
+                             br 0
+                           end
+                         end
+                         local.get 1))"#;
+
+        assert_eq!(
+            invoke(wat, &[Value::I32(5)]).unwrap(),
+            vec![Value::I32(120)]
+        );
+        assert_eq!(
+            invoke(wat, &[Value::I32(13)]).unwrap(),
+            vec![Value::I32(1932053504)]
+        );
+    }
+
+    #[test]
+    fn integer_bitwise() {
+        let results = invoke(
+            r#"(module
+                 (func (result i32 i64 i32 i64 i32)
+                   i32.const 0x8000_0001
+                   i32.const 33
+                   i32.rotl
+                   i64.const -1
+                   i64.const 65
+                   i64.shr_u
+                   i32.const 0x00f0
+                   i32.clz
+                   i64.const 0xff00
+                   i64.popcnt
+                   i64.const -1
+                   i64.const 1
+                   i64.lt_u))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                Value::I32(3),
+                Value::I64(i64::MAX),
+                Value::I32(24),
+                Value::I64(8),
+                Value::I32(0)
+            ]
+        );
+    }
+
+    #[test]
+    fn integer_traps() {
+        let wat = r#"(module
+                       (func (param i32 i32) (result i32)
+                         local.get 0
+                         local.get 1
+                         i32.div_s))"#;
+
+        let err = invoke(wat, &[Value::I32(1), Value::I32(0)]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::IntegerDivideByZero)
+        );
+
+        let err = invoke(wat, &[Value::I32(i32::MIN), Value::I32(-1)]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::IntegerOverflow)
+        );
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        let results = invoke(
+            r#"(module
+                 (func (result f32 f64 f32 f64 i32)
+                   f32.const 1.5
+                   f32.const 2.25
+                   f32.add
+                   f64.const 2
+                   f64.sqrt
+                   f32.const -nan:0x200000
+                   f32.abs
+                   f64.const 0
+                   f64.const 0
+                   f64.div
+                   f64.const nan
+                   f64.const 1
+                   f64.eq))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                Value::F32(3.75f32.to_bits()),
+                Value::F64(2f64.sqrt().to_bits()),
+                Value::F32(0x7fa0_0000),
+                Value::F64(0x7ff8_0000_0000_0000),
+                Value::I32(0)
+            ]
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        let results = invoke(
+            r#"(module
+                 (func (result i32 i64 f32 i32 i64 i32 i32)
+                   i64.const 0x1_0000_0002
+                   i32.wrap_i64
+                   i32.const -1
+                   i64.extend_i32_u
+                   i64.const -1
+                   f32.convert_i64_u
+                   f32.const -0.0
+                   i32.reinterpret_f32
+                   i64.const 0x80
+                   i64.extend8_s
+                   f64.const -1e10
+                   i32.trunc_sat_f64_s
+                   f32.const nan
+                   i32.trunc_sat_f32_u))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                Value::I32(2),
+                Value::I64(0xffff_ffff),
+                Value::F32(18446744073709551616f32.to_bits()),
+                Value::I32(i32::MIN),
+                Value::I64(-128),
+                Value::I32(i32::MIN),
+                Value::I32(0)
+            ]
+        );
+    }
+
+    #[test]
+    fn conversion_traps() {
+        let wat = r#"(module
+                       (func (param f64) (result i32)
+                         local.get 0
+                         i32.trunc_f64_u))"#;
+
+        assert_eq!(
+            invoke(wat, &[Value::F64(1.9f64.to_bits())]).unwrap(),
+            vec![Value::I32(1)]
+        );
+
+        let err = invoke(wat, &[Value::F64(f64::NAN.to_bits())]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::InvalidConversionToInteger)
+        );
+
+        let err = invoke(wat, &[Value::F64((-1f64).to_bits())]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::IntegerOverflow)
+        );
+    }
+
+    #[test]
+    fn recursive_calls() {
+        let wat = r#"(module
+                       (func $fib (param i64) (result i64)
+                         local.get 0
+                         i64.const 2
+                         i64.lt_u
+                         if
+                           local.get 0
+                           return
+                         end
+                         local.get 0
+                         i64.const 1
+                         i64.sub
+                         call $fib
+                         local.get 0
+                         i64.const 2
+                         i64.sub
+                         call $fib
+                         i64.add))"#;
+
+        assert_eq!(invoke(wat, &[Value::I64(0)]).unwrap(), vec![Value::I64(0)]);
+        assert_eq!(invoke(wat, &[Value::I64(1)]).unwrap(), vec![Value::I64(1)]);
+        assert_eq!(
+            invoke(wat, &[Value::I64(20)]).unwrap(),
+            vec![Value::I64(6765)]
+        );
+    }
+
+    #[test]
+    fn ackermann() {
+        let wat = r#"(module
+                       (func $ack (param i32 i32) (result i32)
+                         local.get 0
+                         i32.eqz
+                         if
+                           local.get 1
+                           i32.const 1
+                           i32.add
+                           return
+                         end
+                         local.get 1
+                         i32.eqz
+                         if
+                           local.get 0
+                           i32.const 1
+                           i32.sub
+                           i32.const 1
+                           call $ack
+                           return
+                         end
+                         local.get 0
+                         i32.const 1
+                         i32.sub
+                         local.get 0
+                         local.get 1
+                         i32.const 1
+                         i32.sub
+                         call $ack
+                         call $ack))"#;
+
+        assert_eq!(
+            invoke(wat, &[Value::I32(2), Value::I32(3)]).unwrap(),
+            vec![Value::I32(9)]
+        );
+        assert_eq!(
+            invoke(wat, &[Value::I32(3), Value::I32(3)]).unwrap(),
+            vec![Value::I32(61)]
+        );
+    }
+
+    #[test]
+    fn multi_value_calls() {
+        let results = invoke(
+            r#"(module
+                 (func (result i32 i64 i32)
+                   i32.const 1
+                   i64.const 2
+                   call $swap
+                   call $locals)
+                 (func $swap (param i32 i64) (result i64 i32)
+                   local.get 1
+                   local.get 0)
+                 (func $locals (param i64 i32) (result i32 i64 i32)
+                   (local f32 i32)
+                   block
+                     local.get 3
+                     local.get 0
+                     local.get 1
+                     return
+                   end
+                   unreachable))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(results, vec![Value::I32(0), Value::I64(2), Value::I32(1)]);
+    }
+
+    #[test]
+    fn call_indirect() {
+        let wat = r#"(module
+                       (type $i32_to_i32 (func (param i32) (result i32)))
+                       (table 3 funcref)
+                       (elem (i32.const 0) $double $answer)
+                       (func (param i32 i32) (result i32)
+                         local.get 0
+                         local.get 1
+                         call_indirect (type $i32_to_i32))
+                       (func $double (param i32) (result i32)
+                         local.get 0
+                         i32.const 2
+                         i32.mul)
+                       (func $answer (result i32)
+                         i32.const 42))"#;
+
+        assert_eq!(
+            invoke(wat, &[Value::I32(21), Value::I32(0)]).unwrap(),
+            vec![Value::I32(42)]
+        );
+
+        let trap = |args: &[Value]| {
+            invoke(wat, args)
+                .unwrap_err()
+                .downcast_ref::<Trap>()
+                .unwrap()
+                .code()
+        };
+        assert_eq!(
+            trap(&[Value::I32(1), Value::I32(1)]),
+            TrapCode::IndirectCallTypeMismatch
+        );
+        assert_eq!(
+            trap(&[Value::I32(1), Value::I32(2)]),
+            TrapCode::NullReference
+        );
+        assert_eq!(
+            trap(&[Value::I32(1), Value::I32(3)]),
+            TrapCode::TableOutOfBounds
+        );
+        assert_eq!(
+            trap(&[Value::I32(1), Value::I32(-1)]),
+            TrapCode::TableOutOfBounds
+        );
+    }
+
+    #[test]
+    fn memory_load_store() {
+        let results = invoke(
+            r#"(module
+                 (memory 1)
+                 (func (result i32 i32 i64 i32 f64)
+                   i32.const 0
+                   i32.const 0xdead_beef
+                   i32.store offset=4
+                   i32.const 3
+                   i64.const -1
+                   i64.store8 offset=2
+                   i32.const 8
+                   f64.const 1.5
+                   f64.store
+                   i32.const 0
+                   i32.load offset=4
+                   i32.const 4
+                   i32.load16_s offset=1
+                   i32.const 5
+                   i64.load8_u
+                   i32.const 0
+                   i32.load align=1
+                   i32.const 8
+                   f64.load))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                Value::I32(0xdead_ffef_u32 as i32),
+                Value::I32(0xffff_adff_u32 as i32),
+                Value::I64(0xff),
+                Value::I32(0),
+                Value::F64(1.5f64.to_bits())
+            ]
+        );
+    }
+
+    #[test]
+    fn memory_out_of_bounds() {
+        let wat = r#"(module
+                       (memory 1)
+                       (func (param i32) (result i32)
+                         local.get 0
+                         i32.load offset=1))"#;
+
+        assert_eq!(
+            invoke(wat, &[Value::I32(65531)]).unwrap(),
+            vec![Value::I32(0)]
+        );
+
+        let trap = |addr: i32| {
+            invoke(wat, &[Value::I32(addr)])
+                .unwrap_err()
+                .downcast_ref::<Trap>()
+                .unwrap()
+                .code()
+        };
+        assert_eq!(trap(65532), TrapCode::MemoryOutOfBounds);
+        assert_eq!(trap(-1), TrapCode::MemoryOutOfBounds);
+    }
+
+    #[test]
+    fn data_segments() {
+        let results = invoke(
+            r#"(module
+                 (memory 1)
+                 (func (result i32 i32 i32)
+                   i32.const 0
+                   i32.load
+                   i32.const 65534
+                   i32.load16_u
+                   i32.const 16
+                   i32.load8_u)
+                 (data (i32.const 0) "\01\02\03\04")
+                 (data (i32.const 65534) "\ff\ee")
+                 (data "\05"))"#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(
+            results,
+            vec![Value::I32(0x0403_0201), Value::I32(0xeeff), Value::I32(0)]
+        );
+    }
+
+    #[test]
+    fn data_segments_out_of_bounds() {
+        let err = invoke(
+            r#"(module
+                 (memory 1)
+                 (func)
+                 (data (i32.const 0) "\01")
+                 (data (i32.const 65535) "\01\02"))"#,
+            &[],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::MemoryOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn memory_size_and_grow() {
+        let wat = r#"(module
+                       (memory 1 4)
+                       (func (param i32) (result i32 i32)
+                         local.get 0
+                         memory.grow
+                         memory.size))"#;
+
+        assert_eq!(
+            invoke(wat, &[Value::I32(2)]).unwrap(),
+            vec![Value::I32(1), Value::I32(3)]
+        );
+        assert_eq!(
+            invoke(wat, &[Value::I32(4)]).unwrap(),
+            vec![Value::I32(-1), Value::I32(1)]
+        );
+        assert_eq!(
+            invoke(wat, &[Value::I32(-1)]).unwrap(),
+            vec![Value::I32(-1), Value::I32(1)]
+        );
+    }
+
+    #[test]
+    fn memory_limiter() {
+        struct Limiter;
+
+        impl ResourceLimiter for Limiter {
+            fn memory_growing(
+                &mut self,
+                _addr: Addr,
+                _current: u64,
+                desired: u64,
+                _maximum: Option<u64>,
+            ) -> bool {
+                desired <= 2
+            }
+
+            fn table_growing(
+                &mut self,
+                _addr: Addr,
+                _current: u32,
+                _desired: u32,
+                _maximum: Option<u32>,
+            ) -> bool {
+                true
+            }
+        }
+
+        let bytes = wat::parse_str(
+            r#"(module
+                 (memory 1)
+                 (func (param i32) (result i32)
+                   local.get 0
+                   memory.grow))"#,
+        )
+        .unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let mut store = Store::new();
+        store.set_limiter(Limiter);
+        let instance = store.instantiate(&module, None).unwrap();
+        let addr = Addr::new_unsafe(instance.index(), 0, Slot::Func);
+        let mut grow = |delta| VM::new().invoke(&mut store, addr, &[Value::I32(delta)]);
+
+        assert_eq!(grow(2).unwrap(), vec![Value::I32(-1)]);
+        assert_eq!(grow(1).unwrap(), vec![Value::I32(1)]);
+        assert_eq!(grow(1).unwrap(), vec![Value::I32(-1)]);
+    }
+
+    #[test]
+    fn table_limiter() {
+        struct Limiter {
+            requests: Rc<RefCell<Vec<(u32, u32)>>>,
+        }
+
+        impl ResourceLimiter for Limiter {
+            fn memory_growing(
+                &mut self,
+                _addr: Addr,
+                _current: u64,
+                desired: u64,
+                _maximum: Option<u64>,
+            ) -> bool {
+                desired <= 1
+            }
+
+            fn table_growing(
+                &mut self,
+                _addr: Addr,
+                current: u32,
+                desired: u32,
+                _maximum: Option<u32>,
+            ) -> bool {
+                self.requests.borrow_mut().push((current, desired));
+                desired <= 3
+            }
+        }
+
+        let bytes = wat::parse_str(
+            r#"(module
+                 (table 1 4 funcref)
+                 (func (param i32) (result i32)
+                   ref.null func
+                   local.get 0
+                   table.grow 0))"#,
+        )
+        .unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let mut store = Store::new();
+        let requests = Rc::new(RefCell::new(vec![]));
+        store.set_limiter(Limiter {
+            requests: Rc::clone(&requests),
+        });
+        let instance = store.instantiate(&module, None).unwrap();
+        let addr = Addr::new_unsafe(instance.index(), 0, Slot::Func);
+        let mut grow = |delta| VM::new().invoke(&mut store, addr, &[Value::I32(delta)]);
+
+        assert_eq!(grow(3).unwrap(), vec![Value::I32(-1)]);
+        assert_eq!(grow(2).unwrap(), vec![Value::I32(1)]);
+        // Requests beyond the maximum fail without consulting the limiter
+        assert_eq!(grow(2).unwrap(), vec![Value::I32(-1)]);
+
+        // Creating memories and tables is subject to the limiter as well
+        let bytes = wat::parse_str(r#"(module (memory 2))"#).unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        assert!(store.instantiate(&module, None).is_err());
+        let bytes = wat::parse_str(r#"(module (table 4 funcref))"#).unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        assert!(store.instantiate(&module, None).is_err());
+
+        assert_eq!(*requests.borrow(), vec![(0, 1), (1, 4), (1, 3), (0, 4)]);
+    }
+
+    #[test]
+    fn bulk_memory() {
+        let wat = r#"(module
+                       (memory 1)
+                       (data "\01\02\03\04")
+                       (func (param i32 i32 i32) (result i64)
+                         i32.const 0
+                         i32.const 0
+                         i32.const 4
+                         memory.init 0
+                         local.get 0
+                         local.get 1
+                         local.get 2
+                         memory.copy
+                         i32.const 6
+                         i32.const 0xff
+                         i32.const 2
+                         memory.fill
+                         i32.const 0
+                         i64.load))"#;
+
+        assert_eq!(
+            invoke(wat, &[Value::I32(1), Value::I32(0), Value::I32(4)]).unwrap(),
+            vec![Value::I64(0xffff_0004_0302_0101u64 as i64)]
+        );
+        assert_eq!(
+            invoke(wat, &[Value::I32(0), Value::I32(1), Value::I32(3)]).unwrap(),
+            vec![Value::I64(0xffff_0000_0404_0302u64 as i64)]
+        );
+
+        let err = invoke(wat, &[Value::I32(65535), Value::I32(0), Value::I32(2)]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::MemoryOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn data_drop() {
+        let wat = r#"(module
+                       (memory 1)
+                       (data "\2a")
+                       (data (i32.const 0) "\01")
+                       (func (param i32) (result i32)
+                         data.drop 0
+                         i32.const 0
+                         i32.const 0
+                         local.get 0
+                         memory.init 0
+                         i32.const 0
+                         i32.load8_u))"#;
+
+        // Dropped segments behave as empty segments
+        assert_eq!(invoke(wat, &[Value::I32(0)]).unwrap(), vec![Value::I32(1)]);
+        let err = invoke(wat, &[Value::I32(1)]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::MemoryOutOfBounds)
+        );
+
+        // Active segments are dropped during instantiation
+        let wat = r#"(module
+                       (memory 1)
+                       (data (i32.const 0) "\01")
+                       (func
+                         i32.const 0
+                         i32.const 0
+                         i32.const 1
+                         memory.init 0))"#;
+        let err = invoke(wat, &[]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::MemoryOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn table_instructions() {
+        let wat = r#"(module
+                       (table 2 funcref)
+                       (func $f (result i32) i32.const 42)
+                       (func (param i32) (result i32 i32 i32)
+                         local.get 0
+                         ref.func $f
+                         table.set
+                         ref.null func
+                         i32.const 2
+                         table.grow
+                         table.size
+                         i32.const 1
+                         table.get
+                         ref.is_null)
+                       (elem declare func $f))"#;
+
+        assert_eq!(
+            invoke_nth(wat, 1, &[Value::I32(1)]).unwrap(),
+            vec![Value::I32(2), Value::I32(4), Value::I32(0)]
+        );
+        assert_eq!(
+            invoke_nth(wat, 1, &[Value::I32(0)]).unwrap(),
+            vec![Value::I32(2), Value::I32(4), Value::I32(1)]
+        );
+        let err = invoke_nth(wat, 1, &[Value::I32(2)]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::TableOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn table_grow_maximum() {
+        let wat = r#"(module
+                       (table 1 2 funcref)
+                       (func (param i32) (result i32)
+                         ref.null func
+                         local.get 0
+                         table.grow))"#;
+
+        assert_eq!(invoke(wat, &[Value::I32(2)]).unwrap(), vec![Value::I32(-1)]);
+        assert_eq!(
+            invoke(wat, &[Value::I32(-1)]).unwrap(),
+            vec![Value::I32(-1)]
+        );
+        assert_eq!(invoke(wat, &[Value::I32(1)]).unwrap(), vec![Value::I32(1)]);
+    }
+
+    #[test]
+    fn table_bulk_instructions() {
+        let wat = r#"(module
+                       (type $t (func (result i32)))
+                       (table 4 funcref)
+                       (func $zero (result i32) i32.const 0)
+                       (func $one (result i32) i32.const 1)
+                       (func (param i32 i32 i32) (result i32 i32 i32 i32)
+                         i32.const 0
+                         i32.const 0
+                         i32.const 2
+                         table.init 0
+                         local.get 0
+                         local.get 1
+                         local.get 2
+                         table.copy
+                         i32.const 3
+                         ref.func $one
+                         i32.const 1
+                         table.fill
+                         (call_indirect (type $t) (i32.const 0))
+                         (call_indirect (type $t) (i32.const 1))
+                         (call_indirect (type $t) (i32.const 2))
+                         (call_indirect (type $t) (i32.const 3)))
+                       (elem func $zero $one))"#;
+
+        assert_eq!(
+            invoke_nth(wat, 2, &[Value::I32(1), Value::I32(0), Value::I32(2)]).unwrap(),
+            vec![Value::I32(0), Value::I32(0), Value::I32(1), Value::I32(1)]
+        );
+        let err = invoke_nth(wat, 2, &[Value::I32(3), Value::I32(0), Value::I32(2)]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::TableOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn elem_drop() {
+        let wat = r#"(module
+                       (table 1 funcref)
+                       (func $f)
+                       (elem func $f)
+                       (elem (i32.const 0) func $f)
+                       (func (param i32 i32)
+                         elem.drop 0
+                         i32.const 0
+                         local.get 1
+                         local.get 0
+                         table.init 0))"#;
+
+        // Dropped segments behave as empty segments
+        assert!(invoke_nth(wat, 1, &[Value::I32(0), Value::I32(0)]).is_ok());
+        let err = invoke_nth(wat, 1, &[Value::I32(1), Value::I32(0)]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::TableOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn null_elem_items() {
+        let wat = r#"(module
+                       (type $t (func (result i32)))
+                       (table 4 funcref)
+                       (func $one (result i32) i32.const 1)
+                       (elem (i32.const 0) funcref (ref.func $one) (ref.null func))
+                       (elem funcref (ref.null func) (ref.func $one))
+                       (func (param i32) (result i32)
+                         i32.const 2
+                         i32.const 0
+                         i32.const 2
+                         table.init 1
+                         elem.drop 1
+                         (call_indirect (type $t) (local.get 0))))"#;
+
+        assert_eq!(
+            invoke_nth(wat, 1, &[Value::I32(0)]).unwrap(),
+            vec![Value::I32(1)]
+        );
+        assert_eq!(
+            invoke_nth(wat, 1, &[Value::I32(3)]).unwrap(),
+            vec![Value::I32(1)]
+        );
+        for index in [1, 2] {
+            let err = invoke_nth(wat, 1, &[Value::I32(index)]).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<Trap>().map(Trap::code),
+                Some(TrapCode::NullReference)
+            );
+        }
+    }
+
+    #[test]
+    fn elem_segments_out_of_bounds() {
+        let wat = r#"(module
+                       (table 2 funcref)
+                       (func $f)
+                       (elem (i32.const 1) func $f $f))"#;
+
+        let err = invoke(wat, &[]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::TableOutOfBounds)
+        );
+        assert_eq!(err.to_string(), "Element segment does not fit in table");
+    }
+
+    #[test]
+    fn globals() {
+        let wat = r#"(module
+                       (global $counter (mut i32) (i32.const 40))
+                       (global $step i64 (i64.const 1))
+                       (func (result i32 i64)
+                         global.get $counter
+                         i32.const 1
+                         i32.add
+                         global.set $counter
+                         global.get $counter
+                         i32.const 1
+                         i32.add
+                         global.set $counter
+                         global.get $counter
+                         global.get $step))"#;
+
+        assert_eq!(
+            invoke(wat, &[]).unwrap(),
+            vec![Value::I32(42), Value::I64(1)]
+        );
+    }
+
+    #[test]
+    fn ref_globals() {
+        let wat = r#"(module
+                       (type $t (func (result i32)))
+                       (table 1 funcref)
+                       (global $null funcref (ref.null func))
+                       (global $f funcref (ref.func $f))
+                       (global $ext externref (ref.null extern))
+                       (func $f (result i32) i32.const 7)
+                       (func (param i32) (result i32)
+                         (table.set (i32.const 0) (global.get $f))
+                         (if (local.get 0)
+                           (then (table.set (i32.const 0) (global.get $null))))
+                         (call_indirect (type $t) (i32.const 0)))
+                       (func (result i32 i32)
+                         (ref.is_null (global.get $null))
+                         (ref.is_null (global.get $ext))))"#;
+
+        assert_eq!(
+            invoke_nth(wat, 1, &[Value::I32(0)]).unwrap(),
+            vec![Value::I32(7)]
+        );
+        let err = invoke_nth(wat, 1, &[Value::I32(1)]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::NullReference)
+        );
+        assert_eq!(
+            invoke_nth(wat, 2, &[]).unwrap(),
+            vec![Value::I32(1), Value::I32(1)]
+        );
+    }
+
+    #[test]
+    fn extended_constant_exprs() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (import "env" "g" (global i64))
+                 (global i32 (i32.add (i32.const 1) (i32.mul (i32.const 2) (i32.const 3))))
+                 (global i64 (i64.sub (global.get 0) (i64.const 1))))"#,
+        )
+        .unwrap();
+        assert!(Module::from_binary(&bytes).is_err());
+        let mut config = Config::new();
+        config.wasm_extended_const(true);
+        let module = Module::from_binary_with_config(&bytes, &config).unwrap();
+        let instance = Instance::new(module.func_types(), module.shared_names(), 0);
+        let globals = [Value::I64(i64::MIN)];
+
+        assert_eq!(
+            resolve_constant_expr(&module.globals()[0].init_expr, &globals, &instance).unwrap(),
+            Value::I32(7)
+        );
+        assert_eq!(
+            resolve_constant_expr(&module.globals()[1].init_expr, &globals, &instance).unwrap(),
+            Value::I64(i64::MAX)
+        );
+    }
+
+    #[test]
+    fn imported_globals_in_constant_exprs() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (import "env" "g" (global i32))
+                 (global i32 (global.get 0)))"#,
+        )
+        .unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let init = &module.globals()[0].init_expr;
+        let instance = Instance::new(module.func_types(), module.shared_names(), 0);
+
+        assert_eq!(
+            resolve_constant_expr(init, &[Value::I32(7)], &instance).unwrap(),
+            Value::I32(7)
+        );
+        assert!(resolve_constant_expr(init, &[], &instance).is_err());
+    }
+
+    #[test]
+    fn start_function() {
+        let wat = r#"(module
+                       (global $g (mut i32) (i32.const 1))
+                       (memory 1)
+                       (data (i32.const 0) "\02")
+                       (func (result i32) global.get $g)
+                       (func $start
+                         i32.const 0
+                         i32.load8_u
+                         global.get $g
+                         i32.add
+                         global.set $g)
+                       (start $start))"#;
+
+        assert_eq!(invoke(wat, &[]).unwrap(), vec![Value::I32(3)]);
+    }
+
+    #[test]
+    fn start_function_trap() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (func $start unreachable)
+                 (start $start))"#,
+        )
+        .unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let mut store = Store::new();
+
+        let err = store
+            .instantiate(&module, Some("failing".to_string()))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Start function failed");
+        assert_eq!(
+            err.downcast_ref::<Trap>().map(Trap::code),
+            Some(TrapCode::Unreachable)
+        );
+        assert!(store.instance_by_name("failing").is_none());
+    }
+
+    #[test]
+    fn trap_backtrace() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (func (export "outer") call $middle)
+                 (func $middle nop call $inner)
+                 (func $inner unreachable))"#,
+        )
+        .unwrap();
+        let module = Module::from_binary(&bytes).unwrap();
+        let mut store = Store::new();
+        let instance = store.instantiate(&module, None).unwrap();
+        let outer = instance.get_func(&store, "outer").unwrap();
+
+        let err = outer.call(&mut store, &[]).unwrap_err();
+        let trap = err.downcast_ref::<Trap>().unwrap();
+        assert_eq!(trap.code(), TrapCode::Unreachable);
+
+        let backtrace = trap.backtrace();
+        let funcs: Vec<_> = backtrace.iter().map(|frame| frame.func_index()).collect();
+        assert_eq!(funcs, vec![2, 1, 0]);
+        assert!(backtrace
+            .iter()
+            .all(|frame| frame.instance() == instance.index()));
+        // The offsets point at the unreachable and call opcodes
+        let opcodes: Vec<_> = backtrace
+            .iter()
+            .map(|frame| bytes[frame.offset()])
+            .collect();
+        assert_eq!(opcodes, vec![0x00, 0x10, 0x10]);
+
+        // Function names come from the name section
+        let names: Vec<_> = backtrace.iter().map(|frame| frame.func_name()).collect();
+        assert_eq!(names, vec![Some("inner"), Some("middle"), None]);
+        let display = trap.to_string();
+        let lines: Vec<_> = display.lines().skip(1).collect();
+        assert!(lines[0].starts_with("  0: instance 0 inner @ "));
+        assert!(lines[2].starts_with("  2: instance 0 func[0] @ "));
+    }
+
+    #[test]
+    fn stack_exhaustion() {
+        let err = invoke(r#"(module (func $f call $f))"#, &[]).unwrap_err();
+        let trap = err.downcast_ref::<Trap>().unwrap();
+        assert_eq!(trap.code(), TrapCode::StackExhaustion);
+        assert_eq!(trap.backtrace().len(), MAX_CALL_DEPTH);
+    }
+
+    #[test]
+    fn host_reentrancy_exhaustion() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (import "host" "reenter" (func $reenter))
+                 (func (export "f") call $reenter))"#,
+        )
+        .unwrap();
+        let mut linker = Linker::new();
+        linker
+            .typed_func("host", "reenter", |mut caller: Caller<'_>, ()| {
+                let instance = caller.instance()?.clone();
+                let f = instance.get_func(caller.store(), "f")?;
+                f.call(caller.store_mut(), &[])?;
+                Ok(())
+            })
+            .unwrap();
+        let mut store = Store::new();
+        let instance = linker
+            .instantiate(&mut store, &Module::from_binary(&bytes).unwrap(), None)
+            .unwrap();
+        let f = instance.get_func(&store, "f").unwrap();
+
+        let err = f.call(&mut store, &[]).unwrap_err();
+        let trap = err.downcast_ref::<Trap>().unwrap();
+        assert_eq!(trap.code(), TrapCode::StackExhaustion);
+        assert_eq!(trap.backtrace().len(), MAX_NESTED_INVOCATIONS);
+        assert_eq!(store.call_depth(), (0, 0));
+    }
 
-    let addr = Addr::new_unsafe(instance_index, func_idx, Func::slot());
+    #[test]
+    fn host_reentrancy_without_wasm_frames() {
+        let bytes = wat::parse_str(
+            r#"(module
+                 (import "host" "reenter" (func $reenter))
+                 (export "reenter" (func $reenter)))"#,
+        )
+        .unwrap();
+        let mut linker = Linker::new();
+        linker
+            .typed_func("host", "reenter", |mut caller: Caller<'_>, ()| {
+                let instance = caller.instance()?.clone();
+                let reenter = instance.get_func(caller.store(), "reenter")?;
+                reenter.call(caller.store_mut(), &[])?;
+                Ok(())
+            })
+            .unwrap();
+        let mut store = Store::new();
+        let instance = linker
+            .instantiate(&mut store, &Module::from_binary(&bytes).unwrap(), None)
+            .unwrap();
+        let reenter = instance.get_func(&store, "reenter").unwrap();
 
-    Ok(RefValue::FuncRef(addr))
+        let err = reenter.call(&mut store, &[]).unwrap_err();
+        let trap = err.downcast_ref::<Trap>().unwrap();
+        assert_eq!(trap.code(), TrapCode::StackExhaustion);
+        assert!(trap.backtrace().is_empty());
+        assert_eq!(store.call_depth(), (0, 0));
+    }
 }